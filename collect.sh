@@ -3,10 +3,7 @@ set -uo pipefail
 
 # Collect data for all modules
 failed_modules=()
-modules=$(gh repo list terraform-aws-modules --source --limit 100 --json name --jq '.[].name' | \
-  grep terraform-aws- | \
-  grep -v s3-object | \
-  sed 's/terraform-aws-//g')
+modules=$(target/release/tmd discover --tracked)
 
 for module in $modules; do
   if ! target/release/tmd collect-data --module "$module"; then
@@ -15,6 +12,9 @@ for module in $modules; do
   fi
 done
 
+# Record the latest complete month in the rank history, which the leaderboard compares against
+target/release/tmd rank
+
 # Update graphs
 target/release/tmd graph
 
@@ -0,0 +1,320 @@
+use std::{collections::BTreeMap, fmt::Write as _, path::Path};
+
+use anyhow::Result;
+use chrono::{Datelike, Months, NaiveDate};
+
+use crate::{clock::Clock, config::VersionConfig, registry::Summary};
+
+/// Share of monthly downloads a major version needs to be considered the majority
+const MAJORITY_SHARE: f64 = 50.0;
+
+/// Adoption of a single major version over time
+#[derive(Debug)]
+pub struct MajorAdoption {
+  pub major: u64,
+  /// Date the major version was first released, when known
+  pub created_at: Option<NaiveDate>,
+  /// Percentage of the module's downloads in each complete month that went to this major
+  pub shares: BTreeMap<NaiveDate, f64>,
+  /// First month where this major held at least half of the monthly downloads
+  pub reached_majority_at: Option<NaiveDate>,
+  /// Days from the release of this major to the end of the month it reached the majority
+  pub days_to_majority: Option<i64>,
+}
+
+impl MajorAdoption {
+  pub(crate) fn latest_share(&self) -> f64 {
+    self.shares.values().next_back().copied().unwrap_or(0.0)
+  }
+}
+
+/// Adoption of every major version of a module
+#[derive(Debug)]
+pub struct ModuleAdoption {
+  pub module: String,
+  /// Sorted by major version, oldest first
+  pub majors: Vec<MajorAdoption>,
+}
+
+impl ModuleAdoption {
+  pub(crate) fn newest(&self) -> Option<&MajorAdoption> {
+    self.majors.last()
+  }
+
+  /// Major version with the largest share of downloads in the latest month
+  fn dominant(&self) -> Option<&MajorAdoption> {
+    self
+      .majors
+      .iter()
+      .filter(|m| !m.shares.is_empty())
+      .max_by(|a, b| a.latest_share().total_cmp(&b.latest_share()))
+  }
+
+  /// The dominant major when it is not the newest major
+  fn dominant_old_major(&self) -> Option<&MajorAdoption> {
+    let newest = self.newest()?;
+    self.dominant().filter(|d| d.major != newest.major)
+  }
+}
+
+fn month_start(date: NaiveDate) -> Result<NaiveDate> {
+  NaiveDate::from_ymd_opt(date.year(), date.month(), 1).ok_or_else(|| anyhow::anyhow!("Invalid date: {date}"))
+}
+
+/// Compute the monthly download share of each major version from a module's registry snapshots
+///
+/// Snapshots hold cumulative downloads, so the downloads in a month are the difference between the last snapshot of
+/// that month and the last snapshot of the previous month. The first month has no baseline and is skipped, and a
+/// major that did not exist in the previous month starts from zero.
+fn analyze(module: &str, snapshots: &BTreeMap<NaiveDate, Vec<Summary>>, clock: &Clock) -> Result<ModuleAdoption> {
+  let mut month_end: BTreeMap<NaiveDate, BTreeMap<u64, u64>> = BTreeMap::new();
+  for (date, summary) in snapshots {
+    let totals = summary
+      .iter()
+      .map(|s| (s.major_version.parse::<u64>().unwrap_or(0), s.downloads))
+      .collect();
+    month_end.insert(month_start(*date)?, totals);
+  }
+
+  let months: Vec<NaiveDate> = month_end.keys().copied().collect();
+  let (months, _) = crate::graph::filter_incomplete_month(months, vec![0; month_end.len()], clock);
+
+  let mut shares: BTreeMap<u64, BTreeMap<NaiveDate, f64>> = BTreeMap::new();
+  for window in months.windows(2) {
+    let (previous, current) = (&month_end[&window[0]], &month_end[&window[1]]);
+    let gained: BTreeMap<u64, u64> = current
+      .iter()
+      .map(|(major, total)| (*major, total.saturating_sub(previous.get(major).copied().unwrap_or(0))))
+      .collect();
+
+    let month_total: u64 = gained.values().sum();
+    if month_total == 0 {
+      continue;
+    }
+    for (major, count) in gained {
+      shares
+        .entry(major)
+        .or_default()
+        .insert(window[1], count as f64 / month_total as f64 * 100.0);
+    }
+  }
+
+  // Release dates come from the most recent snapshot
+  let created: BTreeMap<u64, NaiveDate> = snapshots
+    .values()
+    .next_back()
+    .map(|summary| {
+      summary
+        .iter()
+        .filter_map(|s| {
+          let date = NaiveDate::parse_from_str(&s.created_at, "%Y-%m-%d").ok()?;
+          Some((s.major_version.parse::<u64>().ok()?, date))
+        })
+        .collect()
+    })
+    .unwrap_or_default();
+
+  let mut majors = Vec::new();
+  for (major, shares) in shares {
+    let created_at = created.get(&major).copied();
+    let reached_majority_at = shares
+      .iter()
+      .find(|(_, share)| **share >= MAJORITY_SHARE)
+      .map(|(month, _)| *month);
+    let days_to_majority = match (created_at, reached_majority_at) {
+      (Some(created), Some(reached)) if reached >= month_start(created)? => {
+        let month_end = reached + Months::new(1) - chrono::Days::new(1);
+        Some((month_end - created).num_days())
+      }
+      _ => None,
+    };
+
+    majors.push(MajorAdoption {
+      major,
+      created_at,
+      shares,
+      reached_majority_at,
+      days_to_majority,
+    });
+  }
+
+  Ok(ModuleAdoption {
+    module: module.to_string(),
+    majors,
+  })
+}
+
+/// Analyze major version adoption for every module with registry data
+pub(crate) fn collect(data_path: &Path, versions: &VersionConfig, clock: &Clock) -> Result<Vec<ModuleAdoption>> {
+  crate::registry::modules(data_path)?
+    .into_iter()
+    .map(|module| {
+      let snapshots =
+        crate::registry::load_snapshots(&data_path.join("registry").join(&module), &versions.rules(&module))?;
+      analyze(&module, &snapshots, clock)
+    })
+    .collect()
+}
+
+/// Render the adoption analysis as a markdown report
+pub(crate) fn report(adoption: &[ModuleAdoption]) -> Result<String> {
+  let mut out = String::new();
+  writeln!(out, "# Major Version Adoption\n")?;
+
+  writeln!(out, "## Modules where an older major still dominates\n")?;
+  let dominated: Vec<_> = adoption
+    .iter()
+    .filter_map(|m| Some((m, m.dominant_old_major()?, m.newest()?)))
+    .collect();
+  if dominated.is_empty() {
+    writeln!(out, "None\n")?;
+  } else {
+    writeln!(out, "| Module | Dominant | Share | Newest | Share |")?;
+    writeln!(out, "|--------|----------|------:|--------|------:|")?;
+    for (m, dominant, newest) in dominated {
+      writeln!(
+        out,
+        "| {} | v{} | {:.1}% | v{} | {:.1}% |",
+        m.module,
+        dominant.major,
+        dominant.latest_share(),
+        newest.major,
+        newest.latest_share()
+      )?;
+    }
+    writeln!(out)?;
+  }
+
+  for m in adoption.iter().filter(|m| !m.majors.is_empty()) {
+    writeln!(out, "## {}\n", m.module)?;
+    writeln!(
+      out,
+      "| Major | Released | Latest share | 50% share reached | Days to 50% |"
+    )?;
+    writeln!(
+      out,
+      "|-------|----------|-------------:|-------------------|------------:|"
+    )?;
+    for major in m.majors.iter().rev() {
+      let or_dash = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+      writeln!(
+        out,
+        "| v{} | {} | {:.1}% | {} | {} |",
+        major.major,
+        or_dash(major.created_at.map(|d| d.to_string())),
+        major.latest_share(),
+        or_dash(major.reached_majority_at.map(|d| d.format("%Y-%m").to_string())),
+        or_dash(major.days_to_majority.map(|d| d.to_string())),
+      )?;
+    }
+    writeln!(out)?;
+  }
+
+  Ok(out)
+}
+
+/// Chart page with the share of monthly downloads per major version, one section per module
+pub(crate) fn page(
+  data_path: &Path,
+  timestamp: &str,
+  versions: &VersionConfig,
+  clock: &Clock,
+) -> Result<crate::graph::ChartPage> {
+  let sections = collect(data_path, versions, clock)?
+    .into_iter()
+    .map(|m| crate::graph::ChartSection {
+      title: m.module,
+      datasets: m
+        .majors
+        .into_iter()
+        .map(|major| crate::graph::ChartDataset {
+          label: format!("v{}.0", major.major),
+          data: major
+            .shares
+            .iter()
+            .map(|(d, share)| crate::graph::DataPoint {
+              x: d.to_string(),
+              y: share.round() as u64,
+            })
+            .collect(),
+          forecast: None,
+        })
+        .collect(),
+    })
+    .collect();
+
+  Ok(crate::graph::ChartPage {
+    schema_version: crate::graph::SCHEMA_VERSION,
+    title: "Major Version Share of Monthly Downloads (%)".to_string(),
+    updated_at: timestamp.to_string(),
+    sections,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn summary(major: &str, downloads: u64, created_at: &str) -> Summary {
+    Summary {
+      downloads,
+      major_version: major.to_string(),
+      created_at: created_at.to_string(),
+      prerelease_downloads: 0,
+      latest_release_at: String::new(),
+      releases: 0,
+    }
+  }
+
+  fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+  }
+
+  #[test]
+  fn test_analyze_shares_and_majority() {
+    let snapshots = BTreeMap::from([
+      (date(2024, 1, 31), vec![summary("1", 1000, "2020-01-01")]),
+      (
+        date(2024, 2, 29),
+        vec![summary("1", 1300, "2020-01-01"), summary("2", 100, "2024-02-10")],
+      ),
+      (
+        date(2024, 3, 31),
+        vec![summary("1", 1400, "2020-01-01"), summary("2", 400, "2024-02-10")],
+      ),
+    ]);
+
+    let adoption = analyze("vpc", &snapshots, &Clock::as_of(Some(date(2024, 4, 1)))).unwrap();
+    let v1 = &adoption.majors[0];
+    let v2 = &adoption.majors[1];
+
+    // February: v1 gained 300, v2 gained 100
+    assert_eq!(v1.shares[&date(2024, 2, 1)], 75.0);
+    assert_eq!(v2.shares[&date(2024, 2, 1)], 25.0);
+    // March: v1 gained 100, v2 gained 300
+    assert_eq!(v2.shares[&date(2024, 3, 1)], 75.0);
+    assert_eq!(v2.reached_majority_at, Some(date(2024, 3, 1)));
+    assert_eq!(v2.days_to_majority, Some(50));
+    assert!(adoption.dominant_old_major().is_none());
+  }
+
+  #[test]
+  fn test_old_major_dominates() {
+    let snapshots = BTreeMap::from([
+      (
+        date(2024, 1, 31),
+        vec![summary("4", 1000, "2022-01-01"), summary("5", 10, "2024-01-15")],
+      ),
+      (
+        date(2024, 2, 29),
+        vec![summary("4", 1900, "2022-01-01"), summary("5", 110, "2024-01-15")],
+      ),
+    ]);
+
+    let adoption = analyze("eks", &snapshots, &Clock::as_of(Some(date(2024, 3, 1)))).unwrap();
+    assert_eq!(adoption.dominant_old_major().map(|m| m.major), Some(4));
+
+    let report = report(&[adoption]).unwrap();
+    assert!(report.contains("| eks | v4 | 90.0% | v5 | 10.0% |"));
+  }
+}
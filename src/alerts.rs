@@ -0,0 +1,347 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{Result, bail};
+use chrono::{Days, NaiveDate};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use url::Url;
+
+use crate::clock::Clock;
+
+/// Scale factor that makes the median absolute deviation consistent with the standard deviation
+const MAD_SCALE: f64 = 1.4826;
+
+/// Scale factor that makes the mean absolute deviation consistent with the standard deviation
+const MEAN_AD_SCALE: f64 = 1.2533;
+
+/// Minimum number of baseline observations before a point is scored
+const MIN_BASELINE: usize = 7;
+
+/// Settings for the anomaly detection
+#[derive(Debug, Clone)]
+pub struct Detection {
+  /// Number of days before each point used as its baseline
+  pub window: usize,
+  /// Robust z-score above which a point is reported
+  pub threshold: f64,
+  /// Only points within this many days of the end of each series are reported
+  pub days: u64,
+}
+
+impl Default for Detection {
+  fn default() -> Self {
+    Self {
+      window: 28,
+      threshold: 3.5,
+      days: 7,
+    }
+  }
+}
+
+/// A point that deviates from its baseline by more than the threshold
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Alert {
+  pub module: String,
+  /// `views`, `clones` or `downloads`
+  pub metric: String,
+  pub date: String,
+  pub value: f64,
+  /// Median of the baseline window
+  pub baseline: f64,
+  /// Robust z-score, positive for spikes and negative for drops
+  pub score: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlertReport {
+  pub updated_at: String,
+  pub alerts: Vec<Alert>,
+}
+
+fn median(values: &mut [f64]) -> f64 {
+  values.sort_by(f64::total_cmp);
+  let mid = values.len() / 2;
+  if values.len().is_multiple_of(2) {
+    (values[mid - 1] + values[mid]) / 2.0
+  } else {
+    values[mid]
+  }
+}
+
+/// Robust z-score of a value against a baseline, using the median and median absolute deviation
+///
+/// Falls back to the mean absolute deviation when more than half of the baseline is identical, and returns `None`
+/// when the baseline has no spread at all.
+fn robust_score(value: f64, baseline: &[f64]) -> Option<(f64, f64)> {
+  let center = median(&mut baseline.to_vec());
+  let mut deviations: Vec<f64> = baseline.iter().map(|v| (v - center).abs()).collect();
+
+  let mad = median(&mut deviations) * MAD_SCALE;
+  let spread = if mad > 0.0 {
+    mad
+  } else {
+    deviations.iter().sum::<f64>() / deviations.len() as f64 * MEAN_AD_SCALE
+  };
+
+  (spread > 0.0).then(|| (center, (value - center) / spread))
+}
+
+/// Score the most recent points of a daily series against the trailing window before each of them
+fn detect(series: &BTreeMap<NaiveDate, f64>, settings: &Detection) -> Vec<(NaiveDate, f64, f64, f64)> {
+  let Some(last) = series.keys().next_back() else {
+    return Vec::new();
+  };
+  let since = *last - Days::new(settings.days.saturating_sub(1));
+
+  let points: Vec<(&NaiveDate, &f64)> = series.iter().collect();
+  let mut anomalies = Vec::new();
+  for (i, (date, value)) in points.iter().enumerate() {
+    if **date < since {
+      continue;
+    }
+
+    let baseline: Vec<f64> = points[i.saturating_sub(settings.window)..i]
+      .iter()
+      .map(|(_, v)| **v)
+      .collect();
+    if baseline.len() < MIN_BASELINE {
+      continue;
+    }
+
+    if let Some((center, score)) = robust_score(**value, &baseline)
+      && score.abs() >= settings.threshold
+    {
+      anomalies.push((**date, **value, center, score));
+    }
+  }
+
+  anomalies
+}
+
+/// Daily download deltas derived from the cumulative registry snapshots
+///
+/// When snapshots are missing for some days, the difference is spread evenly over every day of the gap, so the
+/// detection window counts days rather than snapshots.
+fn daily_deltas(totals: &BTreeMap<NaiveDate, u64>) -> BTreeMap<NaiveDate, f64> {
+  totals
+    .iter()
+    .zip(totals.iter().skip(1))
+    .flat_map(|((d0, v0), (d1, v1))| {
+      let days = (*d1 - *d0).num_days().max(1);
+      let delta = v1.saturating_sub(*v0) as f64 / days as f64;
+      (1..=days as u64).map(move |n| (*d0 + Days::new(n), delta))
+    })
+    .collect()
+}
+
+/// Run the anomaly detection over the daily views, clones and registry downloads of every module
+pub(crate) fn collect(
+  data_path: &Path,
+  settings: &Detection,
+  versions: &crate::config::VersionConfig,
+  clock: &Clock,
+) -> Result<Vec<Alert>> {
+  let mut series: Vec<(String, &str, BTreeMap<NaiveDate, f64>)> = Vec::new();
+
+  for module in crate::github::modules(data_path)? {
+    for metric in ["views", "clones"] {
+      let daily = crate::github::daily_traffic(data_path, &module, metric)?;
+      series.push((
+        module.clone(),
+        metric,
+        daily.into_iter().map(|(d, v)| (d, v as f64)).collect(),
+      ));
+    }
+  }
+
+  for module in crate::registry::modules(data_path)? {
+    let totals = crate::registry::daily_totals(&data_path.join("registry").join(&module), &versions.rules(&module))?;
+    series.push((module, "downloads", daily_deltas(&totals)));
+  }
+
+  let mut alerts = Vec::new();
+  for (module, metric, mut daily) in series {
+    // Data after the clock's date didn't exist yet when the alerts are regenerated for a past date
+    daily.retain(|date, _| *date <= clock.today());
+    for (date, value, baseline, score) in detect(&daily, settings) {
+      alerts.push(Alert {
+        module: module.clone(),
+        metric: metric.to_string(),
+        date: date.to_string(),
+        value: value.round(),
+        baseline: baseline.round(),
+        score: (score * 10.0).round() / 10.0,
+      });
+    }
+  }
+
+  alerts.sort_by(|a, b| b.score.abs().total_cmp(&a.score.abs()));
+  Ok(alerts)
+}
+
+/// Write the alerts to a JSON file
+pub(crate) fn write(path: &Path, alerts: Vec<Alert>, clock: &Clock) -> Result<()> {
+  let report = AlertReport {
+    updated_at: clock.timestamp(),
+    alerts,
+  };
+
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  info!("Writing {}", path.display());
+  fs::write(path, serde_json::to_string_pretty(&report)?)?;
+
+  Ok(())
+}
+
+/// Human readable summary of the alerts, one line per alert
+pub(crate) fn summary(alerts: &[Alert]) -> String {
+  let mut lines = vec![format!(
+    "{} anomal{} detected in module traffic and downloads",
+    alerts.len(),
+    if alerts.len() == 1 { "y" } else { "ies" }
+  )];
+
+  for a in alerts {
+    let direction = if a.score > 0.0 { "spike" } else { "drop" };
+    lines.push(format!(
+      "• {} {} {direction} on {}: {} (baseline {}, score {:+.1})",
+      a.module, a.metric, a.date, a.value, a.baseline, a.score
+    ));
+  }
+
+  lines.join("\n")
+}
+
+/// Post the alert summary to a Slack-compatible incoming webhook
+pub(crate) async fn notify(webhook: &Url, alerts: &[Alert]) -> Result<()> {
+  let payload = serde_json::json!({ "text": summary(alerts) });
+
+  let resp = Client::builder()
+    .user_agent("Module Download Data")
+    .build()?
+    .post(webhook.clone())
+    .json(&payload)
+    .send()
+    .await?;
+
+  if !resp.status().is_success() {
+    bail!("Webhook returned {}", resp.status());
+  }
+
+  info!("Posted {} alert(s) to webhook", alerts.len());
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+  };
+
+  use super::*;
+
+  fn series(values: &[f64]) -> BTreeMap<NaiveDate, f64> {
+    let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    values
+      .iter()
+      .enumerate()
+      .map(|(i, v)| (start + Days::new(i as u64), *v))
+      .collect()
+  }
+
+  #[test]
+  fn test_detect_spike_and_drop() {
+    let mut values: Vec<f64> = (0..30).map(|i| 100.0 + (i % 5) as f64 * 4.0).collect();
+    values.extend([500.0, 104.0, 2.0]);
+
+    let anomalies = detect(&series(&values), &Detection::default());
+    let dates: Vec<String> = anomalies.iter().map(|a| a.0.to_string()).collect();
+    assert_eq!(dates, vec!["2024-01-31", "2024-02-02"]);
+    assert!(anomalies[0].3 > 0.0);
+    assert!(anomalies[1].3 < 0.0);
+  }
+
+  #[test]
+  fn test_detect_flat_baseline() {
+    let mut values = vec![0.0; 20];
+    values.push(0.0);
+    assert!(detect(&series(&values), &Detection::default()).is_empty());
+  }
+
+  #[test]
+  fn test_daily_deltas_spread_gaps() {
+    let totals = BTreeMap::from([
+      (NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), 100),
+      (NaiveDate::from_ymd_opt(2024, 1, 2).unwrap(), 150),
+      (NaiveDate::from_ymd_opt(2024, 1, 5).unwrap(), 300),
+    ]);
+    let deltas = daily_deltas(&totals);
+    assert_eq!(
+      deltas.values().copied().collect::<Vec<_>>(),
+      vec![50.0, 50.0, 50.0, 50.0]
+    );
+    assert_eq!(
+      deltas.keys().next_back(),
+      Some(&NaiveDate::from_ymd_opt(2024, 1, 5).unwrap())
+    );
+  }
+
+  #[tokio::test]
+  async fn test_notify_posts_slack_payload() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let webhook = Url::parse(&format!("http://{}/hooks/alerts", listener.local_addr().unwrap())).unwrap();
+
+    let server = tokio::spawn(async move {
+      let (mut socket, _) = listener.accept().await.unwrap();
+      let mut request = Vec::new();
+      let mut buf = [0; 4096];
+      // Read until the headers and the `content-length` bytes of body after them have arrived
+      loop {
+        let text = String::from_utf8_lossy(&request);
+        if let Some((headers, body)) = text.split_once("\r\n\r\n") {
+          let length: usize = headers
+            .lines()
+            .find_map(|l| {
+              l.to_lowercase()
+                .strip_prefix("content-length:")
+                .map(|v| v.trim().parse().unwrap())
+            })
+            .unwrap();
+          if body.len() >= length {
+            break;
+          }
+        }
+        let n = socket.read(&mut buf).await.unwrap();
+        assert!(n > 0, "Connection closed before the whole request arrived");
+        request.extend_from_slice(&buf[..n]);
+      }
+      socket
+        .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok")
+        .await
+        .unwrap();
+      String::from_utf8(request).unwrap()
+    });
+
+    let alerts = vec![Alert {
+      module: "eks".to_string(),
+      metric: "views".to_string(),
+      date: "2024-01-31".to_string(),
+      value: 500.0,
+      baseline: 108.0,
+      score: 39.2,
+    }];
+    notify(&webhook, &alerts).await.unwrap();
+
+    let request = server.await.unwrap();
+    assert!(request.starts_with("POST /hooks/alerts HTTP/1.1"));
+    let body: serde_json::Value = serde_json::from_str(request.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+    assert_eq!(
+      body["text"],
+      "1 anomaly detected in module traffic and downloads\n• eks views spike on 2024-01-31: 500 (baseline 108, score +39.2)"
+    );
+  }
+}
@@ -0,0 +1,170 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::Result;
+use chrono::Days;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+  clock::Clock,
+  config::{BadgeConfig, BadgeStyle, VersionConfig},
+};
+
+/// Number of days summed for the weekly views badge
+const WEEK: u64 = 7;
+
+/// shields.io endpoint badge (<https://shields.io/badges/endpoint-badge>)
+#[derive(Debug, Serialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Badge {
+  pub schema_version: u8,
+  pub label: String,
+  pub message: String,
+  pub color: String,
+}
+
+impl Badge {
+  fn new(style: &BadgeStyle, message: String, value: f64) -> Self {
+    Self {
+      schema_version: 1,
+      label: style.label.clone(),
+      message,
+      color: style.color_for(value).to_string(),
+    }
+  }
+}
+
+/// Headline numbers shown on the badges of a single module
+#[derive(Debug, Default)]
+struct Headline {
+  monthly_downloads: Option<u64>,
+  total_downloads: Option<u64>,
+  /// Newest major version and its share of the latest month's downloads
+  major_share: Option<(u64, f64)>,
+  weekly_views: Option<u64>,
+}
+
+impl Headline {
+  /// Badges keyed by file name, for the numbers that are available
+  fn badges(&self, config: &BadgeConfig) -> Vec<(&'static str, Badge)> {
+    let compact = |v: u64| crate::svg::compact(v as f64);
+    let mut badges = Vec::new();
+
+    if let Some(v) = self.monthly_downloads {
+      badges.push((
+        "downloads-month",
+        Badge::new(&config.monthly_downloads, compact(v), v as f64),
+      ));
+    }
+    if let Some(v) = self.total_downloads {
+      badges.push((
+        "downloads-total",
+        Badge::new(&config.total_downloads, compact(v), v as f64),
+      ));
+    }
+    if let Some((major, share)) = self.major_share {
+      badges.push((
+        "major-share",
+        Badge::new(&config.major_share, format!("v{major} {share:.0}%"), share),
+      ));
+    }
+    if let Some(v) = self.weekly_views {
+      badges.push(("views-week", Badge::new(&config.weekly_views, compact(v), v as f64)));
+    }
+
+    badges
+  }
+}
+
+/// Headline numbers of every module, from the data collected up to the clock's current date
+fn headlines(data_path: &Path, versions: &VersionConfig, clock: &Clock) -> Result<BTreeMap<String, Headline>> {
+  let today = clock.today();
+  let mut headlines: BTreeMap<String, Headline> = BTreeMap::new();
+
+  for (module, monthly) in crate::registry::monthly_downloads(data_path, versions, clock)? {
+    let headline = headlines.entry(module.clone()).or_default();
+    headline.monthly_downloads = monthly.values().next_back().copied();
+    headline.total_downloads =
+      crate::registry::daily_totals(&data_path.join("registry").join(&module), &versions.rules(&module))?
+        .range(..=today)
+        .next_back()
+        .map(|(_, v)| *v);
+  }
+
+  for adoption in crate::adoption::collect(data_path, versions, clock)? {
+    if let Some(newest) = adoption.newest() {
+      headlines.entry(adoption.module.clone()).or_default().major_share = Some((newest.major, newest.latest_share()));
+    }
+  }
+
+  for module in crate::github::modules(data_path)? {
+    let daily = crate::github::daily_traffic(data_path, &module, "views")?;
+    if let Some(last) = daily.range(..=today).next_back().map(|(d, _)| *d) {
+      let since = last - Days::new(WEEK - 1);
+      headlines.entry(module).or_default().weekly_views = Some(daily.range(since..=last).map(|(_, v)| v).sum());
+    }
+  }
+
+  Ok(headlines)
+}
+
+/// Output shields.io endpoint badges for every module, as `badges/<module>/<badge>.json`
+pub(crate) fn graph(
+  data_path: &Path,
+  output_path: &Path,
+  clock: &Clock,
+  config: &BadgeConfig,
+  versions: &VersionConfig,
+) -> Result<()> {
+  let headlines = headlines(data_path, versions, clock)?;
+
+  for (module, headline) in &headlines {
+    let module_path = output_path.join("badges").join(module);
+    fs::create_dir_all(&module_path)?;
+
+    for (name, badge) in headline.badges(config) {
+      fs::write(
+        module_path.join(format!("{name}.json")),
+        serde_json::to_string_pretty(&badge)?,
+      )?;
+    }
+  }
+
+  info!("Wrote badges for {} modules", headlines.len());
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_badges() {
+    let headline = Headline {
+      monthly_downloads: Some(1_234_567),
+      total_downloads: None,
+      major_share: Some((21, 44.7)),
+      weekly_views: Some(50),
+    };
+
+    let badges = headline.badges(&BadgeConfig::default());
+    let names: Vec<&str> = badges.iter().map(|(n, _)| *n).collect();
+    assert_eq!(names, vec!["downloads-month", "major-share", "views-week"]);
+    assert_eq!(
+      badges[0].1,
+      Badge {
+        schema_version: 1,
+        label: "downloads/month".to_string(),
+        message: "1.2M".to_string(),
+        color: "brightgreen".to_string(),
+      }
+    );
+    assert_eq!(badges[1].1.message, "v21 45%");
+    assert_eq!(badges[1].1.color, "yellow");
+    assert_eq!(badges[2].1.color, "lightgrey");
+
+    let json = serde_json::to_value(&badges[0].1).unwrap();
+    assert_eq!(json["schemaVersion"], 1);
+  }
+}
@@ -6,6 +6,8 @@ use clap::{Args, Parser, Subcommand, builder::Styles};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
 use serde::{Deserialize, Serialize};
 
+use crate::clock::Clock;
+
 /// Styles for CLI
 fn get_styles() -> Styles {
   Styles::styled()
@@ -33,6 +35,10 @@ pub struct Cli {
   #[command(subcommand)]
   pub command: Commands,
 
+  /// Path to the configuration file
+  #[clap(long, global = true, env = "TMD_CONFIG", default_value = crate::config::DEFAULT_CONFIG)]
+  pub config: PathBuf,
+
   #[clap(flatten)]
   pub verbose: Verbosity<InfoLevel>,
 }
@@ -43,7 +49,40 @@ pub enum Commands {
   CollectData(Module),
 
   /// Generate graphs from the collected data
-  Graph,
+  Graph(Graph),
+
+  /// Rank modules by monthly downloads, views, clones and growth and record the rank history
+  Rank(Rank),
+
+  /// Report how quickly users adopt each new major version of the modules
+  Adoption(Adoption),
+
+  /// Backtest the download and traffic forecasts against the most recent months
+  Forecast(Forecast),
+
+  /// Detect anomalies in the daily views, clones and downloads and optionally post them to a webhook
+  Alerts(Alerts),
+
+  /// Compare traffic and downloads before and after a module release
+  ReleaseImpact(ReleaseImpact),
+
+  /// Render a self-contained static HTML site from the collected data
+  Render(Render),
+
+  /// Serve the collected data over a local JSON API
+  Serve(Serve),
+
+  /// Print the latest downloads and traffic of every module as Prometheus/OpenMetrics gauges
+  Metrics(Metrics),
+
+  /// Export the full download and traffic history to a time-series database
+  Export(Export),
+
+  /// Find repositories and registry modules in the organization that are not tracked yet
+  Discover(Discover),
+
+  /// Report modules with no category or with topic categories that disagree with their assigned ones
+  Categories(Categories),
 }
 
 #[derive(Args, Debug, Deserialize, Serialize)]
@@ -54,12 +93,443 @@ pub struct Module {
 }
 
 impl Module {
-  pub async fn collect(&self, data_path: PathBuf) -> Result<()> {
+  pub async fn collect(&self, data_path: PathBuf, config: &crate::config::Config) -> Result<()> {
     // GitHub data
     crate::github::collect(&data_path, &self.module).await?;
 
     // Terraform registry data
-    crate::registry::collect(&data_path, &self.module).await?;
+    crate::registry::collect(&data_path, &self.module, &config.versions).await?;
+
+    Ok(())
+  }
+}
+
+#[derive(Args, Debug)]
+pub struct Graph {
+  /// Output format of the graphs
+  #[clap(long, value_enum, default_value_t)]
+  pub format: crate::graph::Format,
+
+  /// Size of the time buckets of the registry and traffic charts
+  #[clap(long, value_enum, default_value_t)]
+  pub granularity: crate::graph::Granularity,
+
+  /// First day included in the registry and traffic charts (YYYY-MM-DD)
+  #[clap(long)]
+  pub from: Option<chrono::NaiveDate>,
+
+  /// Last day included in the registry and traffic charts (YYYY-MM-DD)
+  #[clap(long)]
+  pub to: Option<chrono::NaiveDate>,
+
+  /// Generate the graphs as they looked on this date (YYYY-MM-DD, UTC) instead of today
+  #[clap(long)]
+  pub as_of: Option<chrono::NaiveDate>,
+}
+
+impl Graph {
+  pub fn options(&self) -> crate::graph::ChartOptions {
+    crate::graph::ChartOptions {
+      granularity: self.granularity,
+      from: self.from,
+      to: self.to,
+    }
+  }
+
+  pub fn clock(&self) -> Clock {
+    Clock::as_of(self.as_of)
+  }
+}
+
+#[derive(Args, Debug, Deserialize, Serialize)]
+pub struct Rank {
+  /// The month to rank, as YYYY-MM (defaults to the latest complete month)
+  #[clap(long)]
+  month: Option<String>,
+
+  /// Rank the modules as they stood on this date (YYYY-MM-DD, UTC) instead of today
+  #[clap(long)]
+  as_of: Option<chrono::NaiveDate>,
+}
+
+impl Rank {
+  pub fn rank(&self, data_path: PathBuf, config: &crate::config::Config) -> Result<()> {
+    let categories = crate::topics::Categories::load(&data_path, &config.categories)?;
+    let ranking = crate::leaderboard::rank(
+      &data_path,
+      self.month.as_deref(),
+      &categories,
+      &config.versions,
+      &Clock::as_of(self.as_of),
+    )?;
+
+    for category in ranking.categories {
+      println!("\n{} ({})", category.title, ranking.month);
+      println!(
+        "{:>4}  {:<28} {:>12} {:>10} {:>10} {:>9}",
+        "Rank", "Module", "Downloads", "Views", "Clones", "Growth"
+      );
+      for s in category.standings {
+        let growth = s.growth.map(|g| format!("{g:+.1}%")).unwrap_or_else(|| "-".to_string());
+        println!(
+          "{:>4}  {:<28} {:>12} {:>10} {:>10} {:>9}",
+          s.ranks.downloads, s.module, s.downloads, s.views, s.clones, growth
+        );
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Args, Debug, Deserialize, Serialize)]
+pub struct Adoption {
+  /// Write the markdown report to this file instead of stdout
+  #[clap(short, long)]
+  output: Option<PathBuf>,
+
+  /// Report the adoption as it looked on this date (YYYY-MM-DD, UTC) instead of today
+  #[clap(long)]
+  as_of: Option<chrono::NaiveDate>,
+}
+
+impl Adoption {
+  pub fn report(&self, data_path: PathBuf, config: &crate::config::Config) -> Result<()> {
+    let adoption = crate::adoption::collect(&data_path, &config.versions, &Clock::as_of(self.as_of))?;
+    let report = crate::adoption::report(&adoption)?;
+
+    match &self.output {
+      Some(path) => std::fs::write(path, report)?,
+      None => print!("{report}"),
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Args, Debug, Deserialize, Serialize)]
+pub struct Forecast {
+  /// Number of most recent months held out of the fit and compared against the forecast
+  #[clap(long, default_value_t = 3)]
+  holdout: usize,
+
+  /// Backtest the forecasts as they looked on this date (YYYY-MM-DD, UTC) instead of today
+  #[clap(long)]
+  as_of: Option<chrono::NaiveDate>,
+}
+
+impl Forecast {
+  pub fn backtest(&self, data_path: PathBuf, config: &crate::config::Config) -> Result<()> {
+    for (_, title, modules) in crate::forecast::series(&data_path, &config.versions, &Clock::as_of(self.as_of))? {
+      println!("\n{title} - last {} months held out", self.holdout);
+      println!("{:<28} {:>12} {:>8} {:>9}", "Module", "MAE", "MAPE", "Coverage");
+
+      let mut mapes = Vec::new();
+      for (module, history) in modules {
+        let values: Vec<f64> = history.values().map(|v| *v as f64).collect();
+        let Some(result) = crate::forecast::backtest(&values, self.holdout) else {
+          println!("{module:<28} {:>12}", "insufficient history");
+          continue;
+        };
+
+        let mape = result
+          .mape
+          .map(|m| format!("{m:.1}%"))
+          .unwrap_or_else(|| "-".to_string());
+        println!("{module:<28} {:>12.0} {mape:>8} {:>8.0}%", result.mae, result.coverage);
+        mapes.extend(result.mape);
+      }
+
+      if !mapes.is_empty() {
+        mapes.sort_by(f64::total_cmp);
+        println!("Median MAPE: {:.1}%", mapes[mapes.len() / 2]);
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Args, Debug, Deserialize, Serialize)]
+pub struct Alerts {
+  /// File the alerts are written to
+  #[clap(short, long, default_value = "site/public/data/alerts.json")]
+  output: PathBuf,
+
+  /// Only report anomalies within this many days of the latest data
+  #[clap(long, default_value_t = 7)]
+  days: u64,
+
+  /// Number of preceding days used as the baseline for each point
+  #[clap(long, default_value_t = 28)]
+  window: usize,
+
+  /// Robust z-score above which a point is reported
+  #[clap(long, default_value_t = 3.5)]
+  threshold: f64,
+
+  /// Slack-compatible webhook URL the alert summary is posted to
+  #[clap(long, env = "TMD_ALERTS_WEBHOOK")]
+  webhook: Option<url::Url>,
+
+  /// Detect the alerts as they looked on this date (YYYY-MM-DD, UTC) instead of today
+  #[clap(long)]
+  as_of: Option<chrono::NaiveDate>,
+}
+
+impl Alerts {
+  pub async fn run(&self, data_path: PathBuf, config: &crate::config::Config) -> Result<()> {
+    let settings = crate::alerts::Detection {
+      window: self.window,
+      threshold: self.threshold,
+      days: self.days,
+    };
+    let clock = Clock::as_of(self.as_of);
+    let alerts = crate::alerts::collect(&data_path, &settings, &config.versions, &clock)?;
+    println!("{}", crate::alerts::summary(&alerts));
+
+    // Written before notifying, so a webhook outage doesn't lose the alerts
+    crate::alerts::write(&self.output, alerts.clone(), &clock)?;
+
+    if let Some(webhook) = &self.webhook
+      && !alerts.is_empty()
+    {
+      crate::alerts::notify(webhook, &alerts).await?;
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Args, Debug, Deserialize, Serialize)]
+pub struct ReleaseImpact {
+  /// The module that was released
+  #[clap(short, long)]
+  module: String,
+
+  /// The released version, such as 21.0.0
+  #[clap(long)]
+  version: String,
+
+  /// Number of days compared before and after the release
+  #[clap(long, default_value_t = 14)]
+  window: u64,
+
+  /// Also write the comparison as JSON to this file
+  #[clap(short, long)]
+  output: Option<PathBuf>,
+}
+
+impl ReleaseImpact {
+  pub async fn analyze(&self, data_path: PathBuf, config: &crate::config::Config) -> Result<()> {
+    let impact = crate::impact::analyze(&data_path, &self.module, &self.version, self.window, &config.versions).await?;
+    println!("{}", crate::impact::table(&impact));
+
+    if let Some(path) = &self.output {
+      std::fs::write(path, serde_json::to_string_pretty(&impact)?)?;
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Args, Debug, Deserialize, Serialize)]
+pub struct Render {
+  /// Directory the HTML site is written to
+  #[clap(long)]
+  html: PathBuf,
+
+  /// Render the site as it looked on this date (YYYY-MM-DD, UTC) instead of today
+  #[clap(long)]
+  as_of: Option<chrono::NaiveDate>,
+}
+
+impl Render {
+  pub fn render(&self, data_path: PathBuf, config: &crate::config::Config) -> Result<()> {
+    let clock = Clock::as_of(self.as_of);
+    let options = crate::graph::ChartOptions::default();
+    let categories = crate::topics::Categories::load(&data_path, &config.categories)?;
+    let pages = crate::graph::pages(&data_path, &options, &config.versions, &categories, &clock)?;
+    crate::html::render(&pages, &self.html)
+  }
+}
+
+#[derive(Args, Debug, Deserialize, Serialize)]
+pub struct Serve {
+  /// Address the server listens on
+  #[clap(long, default_value = "127.0.0.1:8080")]
+  address: std::net::SocketAddr,
+
+  /// Seconds between checks of the data directory for changes
+  #[clap(long, default_value_t = 30)]
+  poll_interval: u64,
+}
+
+impl Serve {
+  pub async fn serve(&self, data_path: PathBuf, config: &crate::config::Config) -> Result<()> {
+    let poll = std::time::Duration::from_secs(self.poll_interval);
+    crate::server::serve(data_path, config.clone(), self.address, poll).await
+  }
+}
+
+#[derive(Args, Debug, Deserialize, Serialize)]
+pub struct Metrics {
+  /// Write the metrics to this file instead of stdout, such as a node_exporter textfile collector directory
+  #[clap(short, long)]
+  output: Option<PathBuf>,
+}
+
+impl Metrics {
+  pub fn export(&self, data_path: PathBuf, config: &crate::config::Config) -> Result<()> {
+    let categories = crate::topics::Categories::load(&data_path, &config.categories)?;
+    let metrics = crate::metrics::render(&data_path, &categories, &config.versions)?;
+    match &self.output {
+      Some(path) => std::fs::write(path, metrics)?,
+      None => print!("{metrics}"),
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Args, Debug, Deserialize, Serialize)]
+pub struct Export {
+  /// Export format
+  #[clap(long, value_enum, default_value_t)]
+  format: crate::export::Format,
+
+  /// Only export data recorded on or after this date (YYYY-MM-DD)
+  #[clap(long)]
+  since: Option<chrono::NaiveDate>,
+
+  /// File the line protocol is written to (defaults to stdout)
+  #[clap(short, long)]
+  output: Option<PathBuf>,
+
+  /// Base URL of the OpenTelemetry collector's OTLP/HTTP receiver
+  #[clap(long, env = "TMD_OTLP_ENDPOINT", default_value = "http://localhost:4318/")]
+  endpoint: url::Url,
+}
+
+impl Export {
+  pub async fn export(&self, data_path: PathBuf, config: &crate::config::Config) -> Result<()> {
+    let categories = crate::topics::Categories::load(&data_path, &config.categories)?;
+    let points = crate::export::points(&data_path, self.since, &categories, &config.versions)?;
+
+    match self.format {
+      crate::export::Format::Influx => {
+        let lines = crate::export::influx(&points);
+        match &self.output {
+          Some(path) => std::fs::write(path, lines)?,
+          None => print!("{lines}"),
+        }
+      }
+      crate::export::Format::Otlp => crate::export::otlp(&self.endpoint, &points).await?,
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Args, Debug, Deserialize, Serialize)]
+pub struct Discover {
+  /// Add the untracked modules to `[modules] track` in the configuration file
+  #[clap(long)]
+  write: bool,
+
+  /// Exit with an error when untracked modules exist, to fail CI
+  #[clap(long)]
+  check: bool,
+
+  /// Print the tracked modules, one per line, without querying GitHub or the registry
+  #[clap(long, conflicts_with_all = ["write", "check"])]
+  tracked: bool,
+}
+
+impl Discover {
+  pub async fn discover(&self, config_path: &std::path::Path, config: &crate::config::Config) -> Result<()> {
+    if self.tracked {
+      crate::discover::tracked(config).iter().for_each(|m| println!("{m}"));
+      return Ok(());
+    }
+
+    let github = url::Url::parse(crate::discover::GITHUB_API)?;
+    let registry = url::Url::parse(crate::discover::REGISTRY_API)?;
+    let token = std::env::var(crate::github::GITHUB_TOKEN_ENV_VAR).ok();
+    let discovery = crate::discover::discover(&github, &registry, token.as_deref(), config).await?;
+
+    let untracked = discovery.untracked();
+    println!("{:<36} {:>7} {:>9}", "Untracked", "GitHub", "Registry");
+    for module in &untracked {
+      let mark = |set: &std::collections::BTreeSet<String>| if set.contains(*module) { "yes" } else { "-" };
+      println!(
+        "{module:<36} {:>7} {:>9}",
+        mark(&discovery.github),
+        mark(&discovery.registry)
+      );
+    }
+    for module in discovery.missing() {
+      println!("{module} is tracked but no longer published");
+    }
+
+    if untracked.is_empty() {
+      println!("All published modules are tracked");
+    } else if self.write {
+      crate::discover::write(config_path, untracked.iter().copied())?;
+      println!("Added {} modules to {}", untracked.len(), config_path.display());
+    } else if self.check {
+      anyhow::bail!(
+        "{} untracked modules; run `tmd discover --write` to track them",
+        untracked.len()
+      );
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Args, Debug, Deserialize, Serialize)]
+pub struct Categories {
+  /// List the categories of every module, not only the ones that need attention
+  #[clap(long)]
+  all: bool,
+}
+
+impl Categories {
+  pub fn report(&self, data_path: PathBuf, config: &crate::config::Config) -> Result<()> {
+    let assignments = crate::topics::assignments(&data_path, &config.categories)?;
+
+    let uncategorized: Vec<&str> = assignments
+      .iter()
+      .filter(|(_, a)| a.categories().is_empty())
+      .map(|(m, _)| m.as_str())
+      .collect();
+    let conflicts: Vec<_> = assignments.iter().filter(|(_, a)| !a.conflicts().is_empty()).collect();
+
+    if self.all {
+      println!("{:<36} {:<28} {:<28}", "Module", "Categories", "Topic categories");
+      for (module, a) in &assignments {
+        let topics: Vec<&str> = a.topics.iter().copied().collect();
+        println!("{module:<36} {:<28} {:<28}", a.categories().join(","), topics.join(","));
+      }
+      println!();
+    }
+
+    println!("{} modules have no category", uncategorized.len());
+    for module in &uncategorized {
+      println!("  {module}");
+    }
+
+    println!("{} modules have conflicting categories", conflicts.len());
+    for (module, a) in &conflicts {
+      let manual: Vec<&str> = a.manual.iter().copied().collect();
+      let source = if a.overridden { "override" } else { "built-in" };
+      println!(
+        "  {module}: {source} {}, topics suggest {}",
+        manual.join(","),
+        a.conflicts().join(",")
+      );
+    }
 
     Ok(())
   }
@@ -0,0 +1,51 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+/// Source of the current time for incomplete period cutoffs and `updated_at` timestamps, always in UTC
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Clock {
+  /// The system clock
+  #[default]
+  System,
+  /// A fixed point in time, to regenerate output exactly as it looked on a past date
+  Fixed(DateTime<Utc>),
+}
+
+impl Clock {
+  /// Clock fixed at the start of a day, or the system clock when no date is given
+  pub fn as_of(date: Option<NaiveDate>) -> Self {
+    match date {
+      Some(d) => Self::Fixed(d.and_time(NaiveTime::MIN).and_utc()),
+      None => Self::System,
+    }
+  }
+
+  pub fn now(&self) -> DateTime<Utc> {
+    match self {
+      Self::System => Utc::now(),
+      Self::Fixed(time) => *time,
+    }
+  }
+
+  /// Current date in UTC
+  pub fn today(&self) -> NaiveDate {
+    self.now().date_naive()
+  }
+
+  /// Current time formatted for the `updated_at` field of the output pages
+  pub fn timestamp(&self) -> String {
+    self.now().format("%Y-%m-%d %H:%M:%S").to_string()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_as_of() {
+    let clock = Clock::as_of(NaiveDate::from_ymd_opt(2024, 3, 15));
+    assert_eq!(clock.today(), NaiveDate::from_ymd_opt(2024, 3, 15).unwrap());
+    assert_eq!(clock.timestamp(), "2024-03-15 00:00:00");
+    assert_eq!(Clock::as_of(None), Clock::System);
+  }
+}
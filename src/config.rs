@@ -0,0 +1,310 @@
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::error::{Error, Result};
+
+/// Default location of the configuration file
+pub const DEFAULT_CONFIG: &str = "tmd.toml";
+
+/// Settings read from the `tmd.toml` configuration file
+///
+/// Every setting is optional; a missing `tmd.toml` or section falls back to the defaults.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+  pub badges: BadgeConfig,
+  pub modules: ModuleConfig,
+  pub categories: CategoryConfig,
+  pub versions: VersionConfig,
+}
+
+impl Config {
+  /// Load the configuration from a file, using the defaults when the default `tmd.toml` does not exist
+  ///
+  /// A file given explicitly has to exist, so a mistyped `--config` path is reported instead of ignored.
+  pub fn load(path: &Path) -> Result<Self> {
+    match fs::read_to_string(path) {
+      Ok(data) => toml::from_str(&data).map_err(|e| Error::Config {
+        path: path.to_path_buf(),
+        source: Box::new(e),
+      }),
+      Err(e) if e.kind() == io::ErrorKind::NotFound && path == Path::new(DEFAULT_CONFIG) => {
+        debug!("No configuration found at {}, using defaults", path.display());
+        Ok(Self::default())
+      }
+      Err(e) => Err(Error::storage(path, e)),
+    }
+  }
+}
+
+/// Modules tracked in addition to the built-in categories, maintained by `tmd discover --write`
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModuleConfig {
+  pub track: Vec<String>,
+  /// Repositories and registry modules that are never tracked
+  pub ignore: Vec<String>,
+}
+
+/// Category assignment from GitHub repository topics, on top of the built-in categories
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CategoryConfig {
+  /// Category of each repository topic; modules are added to the categories of their topics
+  pub topics: BTreeMap<String, String>,
+  /// Categories of a module, replacing both its built-in and topic categories
+  pub overrides: BTreeMap<String, Vec<String>>,
+}
+
+/// Minimum major version of modules when neither their own nor the default rules set one, such as the pre-v16 EKS
+/// majors
+const MIN_MAJORS: [(&str, u64); 1] = [("eks", 16)];
+
+/// Which major versions are collected and charted
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VersionConfig {
+  /// Rules for every module
+  pub default: VersionRules,
+  /// Rules of individual modules, taking precedence over `default` rule by rule
+  pub modules: BTreeMap<String, VersionRules>,
+}
+
+/// Version rules as written in the configuration, where unset rules fall back to the defaults
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VersionRules {
+  /// Hide major versions below this one
+  pub min_major: Option<u64>,
+  /// Collect and chart 0.x releases
+  pub include_v0: Option<bool>,
+  /// Count prereleases such as `2.0.0-beta.1` towards their major version when collecting
+  pub include_prereleases: Option<bool>,
+  /// Hide major versions with fewer total downloads than this
+  pub min_downloads: Option<u64>,
+}
+
+/// Version rules of a single module
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Versions {
+  pub min_major: u64,
+  pub include_v0: bool,
+  pub include_prereleases: bool,
+  pub min_downloads: u64,
+}
+
+impl Default for Versions {
+  fn default() -> Self {
+    Self {
+      min_major: 0,
+      include_v0: false,
+      include_prereleases: true,
+      min_downloads: 0,
+    }
+  }
+}
+
+impl Versions {
+  /// Whether releases of a major version are saved when collecting
+  ///
+  /// Only the 0.x rule applies here; `min_major` and `min_downloads` hide majors when the data is read, so changing
+  /// them later keeps the history of every major.
+  pub fn collects(&self, major: u64) -> bool {
+    major > 0 || self.include_v0
+  }
+
+  /// Whether a major version is included regardless of its downloads
+  pub fn includes(&self, major: u64) -> bool {
+    self.collects(major) && major >= self.min_major
+  }
+
+  /// Whether a major version is shown, given its latest cumulative downloads
+  pub fn shows(&self, major: u64, downloads: u64) -> bool {
+    self.includes(major) && downloads >= self.min_downloads
+  }
+}
+
+impl VersionConfig {
+  /// Rules of a module: its own rules, then the default rules, then the built-in minimum major
+  pub fn rules(&self, module: &str) -> Versions {
+    let own = self.modules.get(module);
+    let number = |rule: fn(&VersionRules) -> Option<u64>| own.and_then(rule).or_else(|| rule(&self.default));
+    let flag = |rule: fn(&VersionRules) -> Option<bool>| own.and_then(rule).or_else(|| rule(&self.default));
+    let builtin = MIN_MAJORS.iter().find(|(m, _)| *m == module).map(|(_, v)| *v);
+    let defaults = Versions::default();
+
+    Versions {
+      min_major: number(|r| r.min_major).or(builtin).unwrap_or(defaults.min_major),
+      include_v0: flag(|r| r.include_v0).unwrap_or(defaults.include_v0),
+      include_prereleases: flag(|r| r.include_prereleases).unwrap_or(defaults.include_prereleases),
+      min_downloads: number(|r| r.min_downloads).unwrap_or(defaults.min_downloads),
+    }
+  }
+}
+
+/// Labels and colors of the shields.io endpoint badges
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BadgeConfig {
+  pub monthly_downloads: BadgeStyle,
+  pub total_downloads: BadgeStyle,
+  pub major_share: BadgeStyle,
+  pub weekly_views: BadgeStyle,
+}
+
+impl Default for BadgeConfig {
+  fn default() -> Self {
+    Self {
+      monthly_downloads: BadgeStyle::new(
+        "downloads/month",
+        &[
+          (0.0, "red"),
+          (1_000.0, "yellow"),
+          (10_000.0, "green"),
+          (100_000.0, "brightgreen"),
+        ],
+      ),
+      total_downloads: BadgeStyle::new(
+        "downloads",
+        &[
+          (0.0, "red"),
+          (10_000.0, "yellow"),
+          (100_000.0, "green"),
+          (1_000_000.0, "brightgreen"),
+        ],
+      ),
+      major_share: BadgeStyle::new("latest major", &[(0.0, "red"), (25.0, "yellow"), (50.0, "green")]),
+      weekly_views: BadgeStyle::new("views/week", &[(0.0, "lightgrey"), (100.0, "blue")]),
+    }
+  }
+}
+
+/// Badge label and the colors used as the value crosses each threshold
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BadgeStyle {
+  pub label: String,
+  /// Color used when the value is below every threshold
+  #[serde(default = "default_color")]
+  pub color: String,
+  #[serde(default)]
+  pub thresholds: Vec<Threshold>,
+}
+
+fn default_color() -> String {
+  "lightgrey".to_string()
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Threshold {
+  /// Smallest value that gets this color
+  pub min: f64,
+  pub color: String,
+}
+
+impl BadgeStyle {
+  fn new(label: &str, thresholds: &[(f64, &str)]) -> Self {
+    Self {
+      label: label.to_string(),
+      color: default_color(),
+      thresholds: thresholds
+        .iter()
+        .map(|(min, color)| Threshold {
+          min: *min,
+          color: color.to_string(),
+        })
+        .collect(),
+    }
+  }
+
+  /// Color of the highest threshold the value reaches
+  pub fn color_for(&self, value: f64) -> &str {
+    self
+      .thresholds
+      .iter()
+      .filter(|t| value >= t.min)
+      .max_by(|a, b| a.min.total_cmp(&b.min))
+      .map(|t| t.color.as_str())
+      .unwrap_or(&self.color)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_missing_file_uses_defaults() {
+    // The crate root has no tmd.toml, so the default path falls back to the defaults
+    let config = Config::load(Path::new(DEFAULT_CONFIG)).unwrap();
+    assert_eq!(config.badges.monthly_downloads.label, "downloads/month");
+
+    // An explicit path that does not exist is an error
+    let err = Config::load(Path::new("does-not-exist.toml")).unwrap_err();
+    assert!(matches!(err, Error::NotFound { .. }));
+  }
+
+  #[test]
+  fn test_badge_overrides() {
+    let config: Config = toml::from_str(
+      r#"
+      [badges.weekly_views]
+      label = "views"
+      color = "orange"
+      thresholds = [{ min = 500, color = "green" }, { min = 50, color = "yellow" }]
+      "#,
+    )
+    .unwrap();
+
+    let style = &config.badges.weekly_views;
+    assert_eq!(style.color_for(10.0), "orange");
+    assert_eq!(style.color_for(50.0), "yellow");
+    assert_eq!(style.color_for(1_000.0), "green");
+    // Sections that are not configured keep their defaults
+    assert_eq!(config.badges.major_share.label, "latest major");
+  }
+
+  #[test]
+  fn test_version_rules() {
+    let config = Config::default();
+    assert_eq!(config.versions.rules("eks").min_major, 16);
+    assert_eq!(config.versions.rules("vpc"), Versions::default());
+
+    let config: Config = toml::from_str(
+      r#"
+      [versions.default]
+      min_downloads = 100
+      include_prereleases = false
+
+      [versions.modules.eks]
+      min_major = 18
+
+      [versions.modules.vpc]
+      include_v0 = true
+      min_downloads = 0
+      "#,
+    )
+    .unwrap();
+
+    let eks = config.versions.rules("eks");
+    assert_eq!(
+      (eks.min_major, eks.min_downloads, eks.include_prereleases),
+      (18, 100, false)
+    );
+    let vpc = config.versions.rules("vpc");
+    assert!(vpc.include_v0 && vpc.shows(0, 5));
+    assert!(!config.versions.rules("iam").shows(5, 99));
+
+    // A global floor replaces the built-in one
+    let config: Config = toml::from_str("[versions.default]\nmin_major = 18\n").unwrap();
+    assert_eq!(config.versions.rules("eks").min_major, 18);
+  }
+
+  #[test]
+  fn test_unknown_fields_rejected() {
+    assert!(toml::from_str::<Config>("[badge]\nlabel = \"typo\"").is_err());
+  }
+}
@@ -0,0 +1,250 @@
+use std::{collections::BTreeSet, fs, path::Path};
+
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+  graph::{ChartDataset, ChartPage, ChartSection, SCHEMA_VERSION},
+  topics::Categories,
+};
+
+/// Registry, views and clones charts of a single module, written to `modules/<module>.json`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ModuleDetail {
+  pub schema_version: u32,
+  pub module: String,
+  /// The category the module is counted in for category totals
+  pub primary_category: Option<String>,
+  pub categories: Vec<String>,
+  pub repository: String,
+  pub registry: String,
+  pub updated_at: String,
+  /// Registry downloads per major version, followed by the views and clones
+  pub sections: Vec<ChartSection>,
+}
+
+/// Headline numbers of a module for the overview, linking to its detail page
+#[derive(Debug, Serialize, JsonSchema, PartialEq)]
+pub struct IndexEntry {
+  pub module: String,
+  pub primary_category: Option<String>,
+  pub categories: Vec<String>,
+  /// Path of the module's detail page, relative to the index
+  pub path: String,
+  /// Total registry downloads across all major versions
+  pub downloads: u64,
+  /// Views and clones in the latest complete period of the charts
+  pub views: u64,
+  pub clones: u64,
+}
+
+/// Manifest of every module written to `index.json`, so the site can load module pages lazily
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ModuleIndex {
+  pub schema_version: u32,
+  pub updated_at: String,
+  pub modules: Vec<IndexEntry>,
+}
+
+/// Datasets of a module in a page, from the section titled after the module or the module's datasets in `All`,
+/// including its forecasts
+fn datasets(page: &ChartPage, module: &str) -> Vec<ChartDataset> {
+  match page.sections.iter().find(|s| s.title == module) {
+    Some(section) => section.datasets.clone(),
+    None => page
+      .sections
+      .iter()
+      .filter(|s| s.title == "All")
+      .flat_map(|s| {
+        s.datasets
+          .iter()
+          .filter(|d| crate::graph::is_module(d, module))
+          .cloned()
+      })
+      .collect(),
+  }
+}
+
+/// Sum of the latest value of each historical dataset
+fn latest(datasets: &[ChartDataset]) -> u64 {
+  datasets
+    .iter()
+    .filter(|d| d.forecast.is_none())
+    .filter_map(|d| d.data.last())
+    .map(|p| p.y)
+    .sum()
+}
+
+/// Split the chart pages into one detail page per module plus an index of headline numbers
+pub(crate) fn pages(
+  pages: &[(String, ChartPage)],
+  categories: &Categories,
+) -> Result<(ModuleIndex, Vec<ModuleDetail>)> {
+  let registry = crate::graph::find_page(pages, "registry-downloads.json")?;
+  let views = crate::graph::find_page(pages, "github-views.json")?;
+  let clones = crate::graph::find_page(pages, "github-clones.json")?;
+
+  let modules: BTreeSet<&str> = registry
+    .sections
+    .iter()
+    .map(|s| s.title.as_str())
+    .chain(
+      views
+        .sections
+        .iter()
+        .filter(|s| s.title == "All")
+        .flat_map(|s| s.datasets.iter().map(|d| d.label.as_str())),
+    )
+    .collect();
+
+  let mut details = Vec::new();
+  let mut entries = Vec::new();
+  for module in modules {
+    let primary_category = categories.primary(module).map(String::from);
+    let module_categories: Vec<String> = categories.of(module).into_iter().map(String::from).collect();
+    let sections: Vec<ChartSection> = [registry, views, clones]
+      .into_iter()
+      .map(|page| ChartSection {
+        title: page.title.clone(),
+        datasets: datasets(page, module),
+      })
+      .collect();
+
+    entries.push(IndexEntry {
+      module: module.to_string(),
+      primary_category: primary_category.clone(),
+      categories: module_categories.clone(),
+      path: format!("modules/{module}.json"),
+      downloads: latest(&sections[0].datasets),
+      views: latest(&sections[1].datasets),
+      clones: latest(&sections[2].datasets),
+    });
+    details.push(ModuleDetail {
+      schema_version: SCHEMA_VERSION,
+      module: module.to_string(),
+      primary_category,
+      categories: module_categories,
+      repository: format!("https://github.com/terraform-aws-modules/terraform-aws-{module}"),
+      registry: format!("https://registry.terraform.io/modules/terraform-aws-modules/{module}/aws"),
+      updated_at: registry.updated_at.clone(),
+      sections,
+    });
+  }
+
+  let index = ModuleIndex {
+    schema_version: SCHEMA_VERSION,
+    updated_at: registry.updated_at.clone(),
+    modules: entries,
+  };
+  Ok((index, details))
+}
+
+/// Write `index.json` and `modules/<module>.json` from the chart pages
+pub(crate) fn graph(chart_pages: &[(String, ChartPage)], output_path: &Path, categories: &Categories) -> Result<()> {
+  let (index, details) = pages(chart_pages, categories)?;
+
+  let module_path = output_path.join("modules");
+  fs::create_dir_all(&module_path)?;
+  for detail in &details {
+    fs::write(
+      module_path.join(format!("{}.json", detail.module)),
+      serde_json::to_string_pretty(detail)?,
+    )?;
+  }
+  fs::write(output_path.join("index.json"), serde_json::to_string_pretty(&index)?)?;
+
+  info!("Wrote {} module pages and index.json", details.len());
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::graph::DataPoint;
+
+  fn dataset(label: &str, ys: &[u64]) -> ChartDataset {
+    ChartDataset {
+      label: label.to_string(),
+      data: ys
+        .iter()
+        .enumerate()
+        .map(|(i, y)| DataPoint {
+          x: format!("2024-0{}-01", i + 1),
+          y: *y,
+        })
+        .collect(),
+      forecast: None,
+    }
+  }
+
+  fn page(title: &str, sections: Vec<(&str, Vec<ChartDataset>)>) -> ChartPage {
+    ChartPage {
+      schema_version: SCHEMA_VERSION,
+      title: title.to_string(),
+      updated_at: "2024-03-01 00:00:00".to_string(),
+      sections: sections
+        .into_iter()
+        .map(|(title, datasets)| ChartSection {
+          title: title.to_string(),
+          datasets,
+        })
+        .collect(),
+    }
+  }
+
+  #[test]
+  fn test_split_pages() {
+    let traffic = |title: &str, y: u64| {
+      page(
+        title,
+        vec![
+          ("All", vec![dataset("eks", &[1, y]), dataset("vpc", &[y])]),
+          ("Compute", vec![dataset("eks", &[1, y])]),
+        ],
+      )
+    };
+    let pages = vec![
+      ("github-views.json".to_string(), traffic("Repository Page Views", 12)),
+      ("github-clones.json".to_string(), traffic("Repository Clones", 34)),
+      (
+        "registry-downloads.json".to_string(),
+        page(
+          "Terraform Registry Downloads",
+          vec![("eks", vec![dataset("v20.0", &[100, 150]), dataset("v21.0", &[5])])],
+        ),
+      ),
+    ];
+
+    let (index, details) = super::pages(&pages, &Categories::builtin()).unwrap();
+    assert_eq!(
+      index.modules[0],
+      IndexEntry {
+        module: "eks".to_string(),
+        primary_category: Some("compute".to_string()),
+        categories: vec!["compute".to_string()],
+        path: "modules/eks.json".to_string(),
+        downloads: 155,
+        views: 12,
+        clones: 34,
+      }
+    );
+    // Modules with traffic but no registry data still get a page
+    assert_eq!(index.modules[1].module, "vpc");
+    assert_eq!(index.modules[1].downloads, 0);
+
+    let eks = &details[0];
+    let titles: Vec<&str> = eks.sections.iter().map(|s| s.title.as_str()).collect();
+    assert_eq!(
+      titles,
+      vec![
+        "Terraform Registry Downloads",
+        "Repository Page Views",
+        "Repository Clones"
+      ]
+    );
+    assert_eq!(eks.sections[0].datasets.len(), 2);
+    assert_eq!(eks.sections[1].datasets[0].label, "eks");
+  }
+}
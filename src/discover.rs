@@ -0,0 +1,308 @@
+use std::{collections::BTreeSet, fs, path::Path};
+
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use toml_edit::{Array, DocumentMut, Item, Table};
+use tracing::{debug, info};
+use url::Url;
+
+use crate::{
+  config::Config,
+  error::{Error, Result},
+};
+
+/// GitHub organization and registry namespace the modules are published under
+const ORG: &str = "terraform-aws-modules";
+const REPO_PREFIX: &str = "terraform-aws-";
+const PROVIDER: &str = "aws";
+
+/// Results requested per page from both APIs (the maximum GitHub allows)
+const PAGE_SIZE: usize = 100;
+
+pub(crate) const GITHUB_API: &str = "https://api.github.com/";
+pub(crate) const REGISTRY_API: &str = "https://registry.terraform.io/";
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+  name: String,
+  #[serde(default)]
+  archived: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryPage {
+  meta: RegistryMeta,
+  modules: Vec<RegistryModule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryMeta {
+  next_offset: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryModule {
+  name: String,
+  provider: String,
+}
+
+/// Modules published upstream compared against the tracked set
+#[derive(Debug, Default)]
+pub(crate) struct Discovery {
+  /// `terraform-aws-*` repositories in the GitHub organization, without the prefix
+  pub(crate) github: BTreeSet<String>,
+  /// AWS modules in the registry namespace
+  pub(crate) registry: BTreeSet<String>,
+  /// Modules in a built-in category or listed in `[modules] track`
+  pub(crate) tracked: BTreeSet<String>,
+}
+
+impl Discovery {
+  /// Published modules that are not tracked
+  pub(crate) fn untracked(&self) -> BTreeSet<&str> {
+    self
+      .github
+      .union(&self.registry)
+      .filter(|m| !self.tracked.contains(*m))
+      .map(String::as_str)
+      .collect()
+  }
+
+  /// Tracked modules that are no longer published in either place
+  pub(crate) fn missing(&self) -> BTreeSet<&str> {
+    self
+      .tracked
+      .iter()
+      .filter(|m| !self.github.contains(*m) && !self.registry.contains(*m))
+      .map(String::as_str)
+      .collect()
+  }
+}
+
+/// Modules in a built-in category or listed in the configuration, less the ignored ones
+pub(crate) fn tracked(config: &Config) -> BTreeSet<String> {
+  crate::CATEGORIES
+    .values()
+    .flatten()
+    .map(|m| m.to_string())
+    .chain(config.modules.track.iter().cloned())
+    .filter(|m| !config.modules.ignore.contains(m))
+    .collect()
+}
+
+async fn get<T: for<'de> Deserialize<'de>>(request: RequestBuilder, url: &Url, resource: &str) -> Result<T> {
+  let resp = request.send().await.map_err(|e| Error::network(url, e))?;
+  let resp = crate::error::check(url, resource, resp)?;
+  let body = resp.text().await.map_err(|e| Error::network(url, e))?;
+  serde_json::from_str(&body).map_err(|e| Error::parse(url, e))
+}
+
+/// Active `terraform-aws-*` source repositories of the organization, one page at a time
+async fn github_modules(client: &Client, base: &Url, token: Option<&str>) -> Result<BTreeSet<String>> {
+  let endpoint = base
+    .join(&format!("orgs/{ORG}/repos"))
+    .map_err(|e| Error::parse("GitHub API URL", e))?;
+
+  let mut modules = BTreeSet::new();
+  for page in 1.. {
+    let mut url = endpoint.clone();
+    url
+      .query_pairs_mut()
+      .append_pair("type", "sources")
+      .append_pair("per_page", &PAGE_SIZE.to_string())
+      .append_pair("page", &page.to_string());
+
+    let mut request = client
+      .get(url.clone())
+      .header("Accept", "application/vnd.github+json")
+      .header("X-GitHub-Api-Version", "2022-11-28");
+    if let Some(token) = token {
+      request = request.header("Authorization", format!("Bearer {token}"));
+    }
+
+    let repos: Vec<Repository> = get(request, &url, &format!("repositories of {ORG}")).await?;
+    debug!("GitHub page {page}: {} repositories", repos.len());
+    let last = repos.len() < PAGE_SIZE;
+    modules.extend(
+      repos
+        .into_iter()
+        .filter(|r| !r.archived)
+        .filter_map(|r| r.name.strip_prefix(REPO_PREFIX).map(String::from)),
+    );
+    if last {
+      break;
+    }
+  }
+
+  Ok(modules)
+}
+
+/// AWS modules of the registry namespace, following the offsets of the list endpoint
+async fn registry_modules(client: &Client, base: &Url) -> Result<BTreeSet<String>> {
+  let endpoint = base
+    .join(&format!("v1/modules/{ORG}"))
+    .map_err(|e| Error::parse("registry API URL", e))?;
+
+  let mut modules = BTreeSet::new();
+  let mut offset = Some(0);
+  while let Some(current) = offset {
+    let mut url = endpoint.clone();
+    url
+      .query_pairs_mut()
+      .append_pair("provider", PROVIDER)
+      .append_pair("limit", &PAGE_SIZE.to_string())
+      .append_pair("offset", &current.to_string());
+
+    let page: RegistryPage = get(client.get(url.clone()), &url, &format!("registry namespace {ORG}")).await?;
+    debug!("Registry offset {current}: {} modules", page.modules.len());
+    modules.extend(
+      page
+        .modules
+        .into_iter()
+        .filter(|m| m.provider == PROVIDER)
+        .map(|m| m.name),
+    );
+    offset = page.meta.next_offset;
+  }
+
+  Ok(modules)
+}
+
+/// List the modules published to GitHub and the registry and compare them against the tracked set
+pub(crate) async fn discover(github: &Url, registry: &Url, token: Option<&str>, config: &Config) -> Result<Discovery> {
+  let client = Client::builder()
+    .user_agent("Module Download Data")
+    .build()
+    .map_err(|e| Error::network(github, e))?;
+
+  let wanted = |m: &String| !config.modules.ignore.contains(m) && !crate::github::NO_ACCESS.contains(&m.as_str());
+  let discovery = Discovery {
+    github: github_modules(&client, github, token)
+      .await?
+      .into_iter()
+      .filter(wanted)
+      .collect(),
+    registry: registry_modules(&client, registry)
+      .await?
+      .into_iter()
+      .filter(wanted)
+      .collect(),
+    tracked: tracked(config),
+  };
+
+  info!(
+    "Found {} repositories and {} registry modules",
+    discovery.github.len(),
+    discovery.registry.len()
+  );
+  Ok(discovery)
+}
+
+/// Add modules to `[modules] track` in the configuration file, keeping the rest of the file as written
+pub(crate) fn write<'a>(path: &Path, modules: impl IntoIterator<Item = &'a str>) -> Result<()> {
+  let data = match fs::read_to_string(path) {
+    Ok(data) => data,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+    Err(e) => return Err(Error::storage(path, e)),
+  };
+  let mut doc: DocumentMut = data.parse().map_err(|e| Error::parse(path.display(), e))?;
+
+  let table = doc
+    .entry("modules")
+    .or_insert_with(|| Item::Table(Table::new()))
+    .as_table_mut()
+    .ok_or_else(|| Error::parse(path.display(), "`modules` is not a table"))?;
+  let track = table
+    .entry("track")
+    .or_insert_with(|| Item::Value(Array::new().into()))
+    .as_array_mut()
+    .ok_or_else(|| Error::parse(path.display(), "`modules.track` is not an array"))?;
+
+  let mut tracked: BTreeSet<String> = track.iter().filter_map(|v| v.as_str().map(String::from)).collect();
+  tracked.extend(modules.into_iter().map(String::from));
+  *track = tracked.iter().collect();
+
+  fs::write(path, doc.to_string()).map_err(|e| Error::storage(path, e))
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{Json, Router, extract::Query, routing::get};
+  use serde_json::{Value, json};
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_discover() {
+    // Stand-in for both APIs: 101 GitHub repositories over two pages and a registry namespace over two offsets
+    let app = Router::new()
+      .route(
+        "/orgs/terraform-aws-modules/repos",
+        get(|Query(q): Query<Vec<(String, String)>>| async move {
+          let page = q.iter().find(|(k, _)| k == "page").map(|(_, v)| v.as_str());
+          let repos: Vec<Value> = match page {
+            Some("1") => (0..PAGE_SIZE)
+              .map(|i| json!({ "name": format!("terraform-aws-m{i:03}"), "archived": false }))
+              .collect(),
+            _ => vec![
+              json!({ "name": "terraform-aws-new", "archived": false }),
+              json!({ "name": "terraform-aws-old", "archived": true }),
+              json!({ "name": "meta", "archived": false }),
+            ],
+          };
+          Json(repos)
+        }),
+      )
+      .route(
+        "/v1/modules/terraform-aws-modules",
+        get(|Query(q): Query<Vec<(String, String)>>| async move {
+          match q.iter().find(|(k, _)| k == "offset").map(|(_, v)| v.as_str()) {
+            Some("0") => Json(json!({
+              "meta": { "next_offset": 100 },
+              "modules": [{ "name": "vpc", "provider": "aws" }, { "name": "vpc", "provider": "google" }],
+            })),
+            _ => Json(json!({ "meta": {}, "modules": [{ "name": "registry-only", "provider": "aws" }] })),
+          }
+        }),
+      );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+    tokio::spawn(axum::serve(listener, app).into_future());
+
+    let mut config = Config::default();
+    config.modules.ignore.push("m000".to_string());
+    let discovery = discover(&base, &base, None, &config).await.unwrap();
+
+    assert_eq!(discovery.github.len(), PAGE_SIZE);
+    assert!(discovery.github.contains("new") && !discovery.github.contains("old"));
+    assert_eq!(
+      discovery.registry,
+      BTreeSet::from(["vpc".to_string(), "registry-only".to_string()])
+    );
+
+    let untracked = discovery.untracked();
+    assert!(untracked.contains("new") && untracked.contains("registry-only"));
+    assert!(!untracked.contains("vpc") && !untracked.contains("m000"));
+    assert!(discovery.missing().contains("eks"));
+  }
+
+  #[test]
+  fn test_write_keeps_config() {
+    let path = std::env::temp_dir().join(format!("tmd-discover-{}.toml", std::process::id()));
+    fs::write(
+      &path,
+      "# Badge colors\n[badges.weekly_views]\nlabel = \"views\"\n\n[modules]\ntrack = [\"b\"]\n",
+    )
+    .unwrap();
+
+    write(&path, ["c", "a"]).unwrap();
+    let data = fs::read_to_string(&path).unwrap();
+    assert!(data.starts_with("# Badge colors\n"));
+
+    let config: Config = toml::from_str(&data).unwrap();
+    assert_eq!(config.modules.track, vec!["a", "b", "c"]);
+    assert_eq!(config.badges.weekly_views.label, "views");
+
+    fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,139 @@
+use std::{
+  fmt::Display,
+  io,
+  path::{Path, PathBuf},
+};
+
+use reqwest::{Response, StatusCode, header::HeaderMap};
+use url::Url;
+
+/// Errors returned by the library, with the module, file or URL they relate to
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+  /// A request failed to send, or the API answered with an unexpected status
+  #[error("Request to {url} failed")]
+  Network {
+    url: Url,
+    #[source]
+    source: reqwest::Error,
+  },
+  /// No token is configured, or the token is not allowed to read the resource
+  #[error("Not authorized to read {resource}: {reason}")]
+  Auth { resource: String, reason: String },
+  /// The API rate limit is exhausted
+  #[error("Rate limited by {url}{}", retry_after.map(|s| format!(", retry after {s}s")).unwrap_or_default())]
+  RateLimited { url: Url, retry_after: Option<u64> },
+  /// A module, version, category or file that does not exist
+  #[error("{what} not found")]
+  NotFound { what: String },
+  /// An API response or data file with unexpected content
+  #[error("Failed to parse {location}")]
+  Parse {
+    location: String,
+    #[source]
+    source: Box<dyn std::error::Error + Send + Sync>,
+  },
+  /// Reading or writing the data directory failed
+  #[error("Failed to access {}", path.display())]
+  Storage {
+    path: PathBuf,
+    #[source]
+    source: io::Error,
+  },
+  /// The configuration file is not valid
+  #[error("Invalid configuration in {}", path.display())]
+  Config {
+    path: PathBuf,
+    #[source]
+    source: Box<toml::de::Error>,
+  },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+  pub(crate) fn parse(location: impl Display, source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+    Self::Parse {
+      location: location.to_string(),
+      source: source.into(),
+    }
+  }
+
+  /// Filesystem error for a path, reported as `NotFound` when the path does not exist
+  pub(crate) fn storage(path: &Path, source: io::Error) -> Self {
+    match source.kind() {
+      io::ErrorKind::NotFound => Self::NotFound {
+        what: path.display().to_string(),
+      },
+      _ => Self::Storage {
+        path: path.to_path_buf(),
+        source,
+      },
+    }
+  }
+
+  pub(crate) fn network(url: &Url, source: reqwest::Error) -> Self {
+    Self::Network {
+      url: url.clone(),
+      source,
+    }
+  }
+}
+
+/// Seconds to wait before retrying, from the `retry-after` or GitHub's `x-ratelimit-reset` headers
+fn retry_after(headers: &HeaderMap) -> Option<u64> {
+  let header = |name| headers.get(name)?.to_str().ok()?.parse::<u64>().ok();
+  header("retry-after").or_else(|| {
+    let reset = header("x-ratelimit-reset")?;
+    Some(reset.saturating_sub(chrono::Utc::now().timestamp().max(0) as u64))
+  })
+}
+
+/// Map an unsuccessful response to the matching error; `resource` describes what was requested
+pub(crate) fn check(url: &Url, resource: &str, resp: Response) -> Result<Response> {
+  let status = resp.status();
+  let headers = resp.headers();
+  let exhausted = headers.get("x-ratelimit-remaining").is_some_and(|v| v == "0");
+
+  if status == StatusCode::TOO_MANY_REQUESTS || (status == StatusCode::FORBIDDEN && exhausted) {
+    return Err(Error::RateLimited {
+      url: url.clone(),
+      retry_after: retry_after(headers),
+    });
+  }
+  match status {
+    StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(Error::Auth {
+      resource: resource.to_string(),
+      reason: format!("the API returned {status}"),
+    }),
+    StatusCode::NOT_FOUND => Err(Error::NotFound {
+      what: resource.to_string(),
+    }),
+    _ => resp.error_for_status().map_err(|e| Error::network(url, e)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_storage_not_found() {
+    let path = Path::new("data/registry/missing");
+    let err = Error::storage(path, io::Error::from(io::ErrorKind::NotFound));
+    assert!(matches!(err, Error::NotFound { .. }));
+    assert_eq!(err.to_string(), "data/registry/missing not found");
+
+    let err = Error::storage(path, io::Error::from(io::ErrorKind::PermissionDenied));
+    assert!(matches!(err, Error::Storage { .. }));
+  }
+
+  #[test]
+  fn test_retry_after() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(retry_after(&headers), None);
+    headers.insert("retry-after", "30".parse().unwrap());
+    assert_eq!(retry_after(&headers), Some(30));
+  }
+}
@@ -0,0 +1,271 @@
+use std::{collections::BTreeMap, fmt::Write as _, path::Path};
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::info;
+use url::Url;
+
+use crate::{config::VersionConfig, topics::Categories};
+
+/// Number of data points sent in each OTLP request, to stay below collector request size limits
+const OTLP_BATCH: usize = 5_000;
+
+/// Target of the time-series export
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+  /// InfluxDB line protocol
+  #[default]
+  Influx,
+  /// OpenTelemetry metrics pushed over OTLP/HTTP
+  Otlp,
+}
+
+/// A single measurement of a module at a point in time
+#[derive(Debug)]
+pub(crate) struct Point {
+  measurement: &'static str,
+  tags: Vec<(&'static str, String)>,
+  fields: Vec<(&'static str, u64)>,
+  time: DateTime<Utc>,
+}
+
+impl Point {
+  /// InfluxDB line protocol with nanosecond timestamps
+  fn line(&self) -> String {
+    let mut line = self.measurement.to_string();
+    for (k, v) in &self.tags {
+      let _ = write!(line, ",{k}={}", escape_tag(v));
+    }
+    let fields: Vec<String> = self.fields.iter().map(|(k, v)| format!("{k}={v}i")).collect();
+    let _ = write!(line, " {} {}", fields.join(","), nanos(&self.time));
+    line
+  }
+}
+
+fn nanos(time: &DateTime<Utc>) -> i64 {
+  time.timestamp_nanos_opt().unwrap_or_default()
+}
+
+/// Escape commas, spaces and equals signs in a line protocol tag value
+fn escape_tag(value: &str) -> String {
+  value.replace(',', r"\,").replace(' ', r"\ ").replace('=', r"\=")
+}
+
+/// Module and primary category tags, like the metrics labels; modules without a category have no category tag, as
+/// empty tag values are invalid
+fn module_tags(module: &str, categories: &Categories) -> Vec<(&'static str, String)> {
+  let mut tags = vec![("module", module.to_string())];
+  if let Some(category) = categories.primary(module) {
+    tags.push(("category", category.to_string()));
+  }
+  tags
+}
+
+/// Every registry snapshot and traffic entry on or after `since`, with their original timestamps
+pub(crate) fn points(
+  data_path: &Path,
+  since: Option<NaiveDate>,
+  categories: &Categories,
+  versions: &VersionConfig,
+) -> Result<Vec<Point>> {
+  let since = since.unwrap_or(NaiveDate::MIN);
+  let mut points = Vec::new();
+
+  for module in crate::registry::modules(data_path)? {
+    for (date, summary) in
+      crate::registry::load_snapshots(&data_path.join("registry").join(&module), &versions.rules(&module))?
+        .range(since..)
+    {
+      for s in summary {
+        let mut tags = module_tags(&module, categories);
+        tags.push(("major", s.major_version.clone()));
+        points.push(Point {
+          measurement: "tmd_registry",
+          tags,
+          fields: vec![("downloads", s.downloads)],
+          time: date.and_time(chrono::NaiveTime::MIN).and_utc(),
+        });
+      }
+    }
+  }
+
+  for module in crate::github::modules(data_path)? {
+    for (data_type, measurement) in [("views", "tmd_github_views"), ("clones", "tmd_github_clones")] {
+      for entry in crate::github::load_traffic(data_path, &module, data_type)?.values() {
+        let time = DateTime::parse_from_rfc3339(&entry.timestamp)
+          .context("Failed to parse timestamp")?
+          .to_utc();
+        if time.date_naive() < since {
+          continue;
+        }
+        points.push(Point {
+          measurement,
+          tags: module_tags(&module, categories),
+          fields: vec![("count", entry.count), ("uniques", entry.uniques)],
+          time,
+        });
+      }
+    }
+  }
+
+  Ok(points)
+}
+
+/// Points in the InfluxDB line protocol, one line per point
+pub(crate) fn influx(points: &[Point]) -> String {
+  points.iter().map(|p| p.line() + "\n").collect()
+}
+
+/// OTLP/HTTP JSON request body with one gauge per measurement field, such as `tmd_registry.downloads`
+fn otlp_request(points: &[Point]) -> Value {
+  let mut metrics: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+  for p in points {
+    let attributes: Vec<Value> = p
+      .tags
+      .iter()
+      .map(|(k, v)| json!({ "key": k, "value": { "stringValue": v } }))
+      .collect();
+    for (field, value) in &p.fields {
+      metrics
+        .entry(format!("{}.{field}", p.measurement))
+        .or_default()
+        .push(json!({
+          "attributes": attributes,
+          // 64-bit integers are encoded as strings in OTLP JSON
+          "timeUnixNano": nanos(&p.time).to_string(),
+          "asInt": value.to_string(),
+        }));
+    }
+  }
+
+  let metrics: Vec<Value> = metrics
+    .into_iter()
+    .map(|(name, data_points)| json!({ "name": name, "gauge": { "dataPoints": data_points } }))
+    .collect();
+
+  json!({
+    "resourceMetrics": [{
+      "resource": { "attributes": [{ "key": "service.name", "value": { "stringValue": "terraform-module-data" } }] },
+      "scopeMetrics": [{ "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") }, "metrics": metrics }],
+    }]
+  })
+}
+
+/// Metrics endpoint of a collector, keeping a path prefix such as `/otel` with or without a trailing slash
+fn metrics_url(endpoint: &Url) -> Result<Url> {
+  let mut base = endpoint.clone();
+  if !base.path().ends_with('/') {
+    base.set_path(&format!("{}/", base.path()));
+  }
+  Ok(base.join("v1/metrics")?)
+}
+
+/// Push points to an OpenTelemetry collector's OTLP/HTTP metrics endpoint (`<endpoint>/v1/metrics`)
+pub(crate) async fn otlp(endpoint: &Url, points: &[Point]) -> Result<()> {
+  let url = metrics_url(endpoint)?;
+  let client = Client::builder().user_agent("Module Download Data").build()?;
+
+  for batch in points.chunks(OTLP_BATCH) {
+    let resp = client.post(url.clone()).json(&otlp_request(batch)).send().await?;
+    if !resp.status().is_success() {
+      bail!("OTLP collector returned {}", resp.status());
+    }
+  }
+
+  info!("Pushed {} points to {url}", points.len());
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  use axum::{Json, Router, extract::State, routing::post};
+
+  use super::*;
+
+  fn point() -> Point {
+    Point {
+      measurement: "tmd_github_views",
+      tags: module_tags("vpc", &Categories::builtin()),
+      fields: vec![("count", 12), ("uniques", 3)],
+      time: "2024-01-02T00:00:00Z".parse().unwrap(),
+    }
+  }
+
+  #[test]
+  fn test_line_protocol() {
+    assert_eq!(
+      influx(&[point()]),
+      "tmd_github_views,module=vpc,category=networking count=12i,uniques=3i 1704153600000000000\n"
+    );
+    assert_eq!(escape_tag("compute,serverless"), r"compute\,serverless");
+
+    // Modules in several categories are tagged with their primary category only
+    assert_eq!(
+      module_tags("lambda", &Categories::builtin()),
+      [("module", "lambda".to_string()), ("category", "serverless".to_string())]
+    );
+
+    // Tracked modules without a category have no category tag rather than an empty one
+    let point = Point {
+      tags: module_tags("new-module", &Categories::builtin()),
+      ..point()
+    };
+    assert_eq!(
+      influx(&[point]),
+      "tmd_github_views,module=new-module count=12i,uniques=3i 1704153600000000000\n"
+    );
+  }
+
+  #[test]
+  fn test_metrics_url() {
+    for (endpoint, expected) in [
+      ("http://localhost:4318/", "http://localhost:4318/v1/metrics"),
+      ("http://localhost:4318", "http://localhost:4318/v1/metrics"),
+      ("http://host:4318/otel", "http://host:4318/otel/v1/metrics"),
+      ("http://host:4318/otel/", "http://host:4318/otel/v1/metrics"),
+    ] {
+      assert_eq!(metrics_url(&Url::parse(endpoint).unwrap()).unwrap().as_str(), expected);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_otlp_push() {
+    // Local stand-in for an OpenTelemetry collector that records the request bodies
+    let received: Arc<Mutex<Vec<Value>>> = Arc::default();
+    let app = Router::new()
+      .route(
+        "/v1/metrics",
+        post(
+          |State(received): State<Arc<Mutex<Vec<Value>>>>, Json(body): Json<Value>| async move {
+            received.lock().unwrap().push(body);
+            Json(json!({}))
+          },
+        ),
+      )
+      .with_state(received.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+    tokio::spawn(axum::serve(listener, app).into_future());
+
+    otlp(&endpoint, &[point()]).await.unwrap();
+
+    let received = received.lock().unwrap();
+    let metrics = &received[0]["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+    assert_eq!(metrics[0]["name"], "tmd_github_views.count");
+    assert_eq!(metrics[1]["name"], "tmd_github_views.uniques");
+    let data_point = &metrics[0]["gauge"]["dataPoints"][0];
+    assert_eq!(data_point["asInt"], "12");
+    assert_eq!(data_point["timeUnixNano"], "1704153600000000000");
+    assert_eq!(
+      data_point["attributes"][0],
+      json!({ "key": "module", "value": { "stringValue": "vpc" } })
+    );
+  }
+}
@@ -0,0 +1,418 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Result;
+use chrono::{Months, NaiveDate};
+use tracing::debug;
+
+use crate::{
+  clock::Clock,
+  graph::{ChartDataset, ChartPage, DataPoint, ForecastBand},
+};
+
+/// Months in a seasonal cycle
+const SEASON: usize = 12;
+
+/// Minimum number of observations required to fit a model
+const MIN_OBSERVATIONS: usize = 4;
+
+/// z-score for the 95% prediction interval
+const Z_95: f64 = 1.96;
+
+/// Smoothing parameters searched when fitting a model
+const GRID: [f64; 9] = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9];
+
+/// Months forecast past the end of the history when generating graphs
+pub(crate) const DEFAULT_HORIZON: usize = 6;
+
+pub(crate) type ModuleSeries = BTreeMap<String, BTreeMap<NaiveDate, u64>>;
+
+/// Predicted values with the lower and upper bounds of the 95% prediction interval
+#[derive(Debug)]
+pub struct Forecast {
+  pub values: Vec<f64>,
+  pub lower: Vec<f64>,
+  pub upper: Vec<f64>,
+}
+
+/// Exponential smoothing model fitted to a series
+///
+/// Holt-Winters (additive seasonality) is used once there are two full seasons of history, otherwise Holt's linear
+/// trend method.
+#[derive(Debug)]
+struct Model {
+  level: f64,
+  trend: f64,
+  /// Seasonal offsets, empty for the non-seasonal model
+  seasonal: Vec<f64>,
+  /// Number of observations the model was fitted to
+  observations: usize,
+  /// Standard deviation of the one step ahead errors
+  sigma: f64,
+}
+
+impl Model {
+  fn fit(series: &[f64]) -> Option<Self> {
+    if series.len() < MIN_OBSERVATIONS {
+      return None;
+    }
+
+    let seasonal = series.len() >= 2 * SEASON;
+    let gammas: &[f64] = if seasonal { &GRID } else { &[0.0] };
+
+    let mut best: Option<(f64, Model)> = None;
+    for &alpha in &GRID {
+      for &beta in &GRID {
+        for &gamma in gammas {
+          let (sse, model) = Self::smooth(series, alpha, beta, gamma, seasonal);
+          if best.as_ref().is_none_or(|(b, _)| sse < *b) {
+            best = Some((sse, model));
+          }
+        }
+      }
+    }
+
+    best.map(|(_, m)| m)
+  }
+
+  fn smooth(series: &[f64], alpha: f64, beta: f64, gamma: f64, seasonal: bool) -> (f64, Model) {
+    let mean = |s: &[f64]| s.iter().sum::<f64>() / s.len() as f64;
+
+    let (mut level, mut trend, mut season, start) = if seasonal {
+      let first = mean(&series[..SEASON]);
+      let second = mean(&series[SEASON..2 * SEASON]);
+      let season: Vec<f64> = series[..SEASON].iter().map(|y| y - first).collect();
+      (first, (second - first) / SEASON as f64, season, SEASON)
+    } else {
+      (series[0], series[1] - series[0], Vec::new(), 1)
+    };
+
+    let mut errors = Vec::new();
+    for (t, y) in series.iter().enumerate().skip(start) {
+      let s = season.get(t % SEASON).copied().unwrap_or(0.0);
+      errors.push(y - (level + trend + s));
+
+      let previous = level;
+      level = alpha * (y - s) + (1.0 - alpha) * (level + trend);
+      trend = beta * (level - previous) + (1.0 - beta) * trend;
+      if let Some(s) = season.get_mut(t % SEASON) {
+        *s = gamma * (y - level) + (1.0 - gamma) * *s;
+      }
+    }
+
+    let sse: f64 = errors.iter().map(|e| e * e).sum();
+    let sigma = (sse / errors.len().max(1) as f64).sqrt();
+
+    (
+      sse,
+      Model {
+        level,
+        trend,
+        seasonal: season,
+        observations: series.len(),
+        sigma,
+      },
+    )
+  }
+
+  fn forecast(&self, horizon: usize) -> Forecast {
+    let mut forecast = Forecast {
+      values: Vec::with_capacity(horizon),
+      lower: Vec::with_capacity(horizon),
+      upper: Vec::with_capacity(horizon),
+    };
+
+    for h in 1..=horizon {
+      let s = if self.seasonal.is_empty() {
+        0.0
+      } else {
+        self.seasonal[(self.observations + h - 1) % SEASON]
+      };
+      // Counts can't be negative, so clamp the prediction and its interval at zero
+      let value = (self.level + h as f64 * self.trend + s).max(0.0);
+      let band = Z_95 * self.sigma * (h as f64).sqrt();
+
+      forecast.values.push(value);
+      forecast.lower.push((value - band).max(0.0));
+      forecast.upper.push(value + band);
+    }
+
+    forecast
+  }
+}
+
+/// Forecast a series `horizon` steps ahead, if there is enough history to fit a model
+pub fn forecast(series: &[f64], horizon: usize) -> Option<Forecast> {
+  Model::fit(series).map(|m| m.forecast(horizon))
+}
+
+/// Forecast accuracy over the held out tail of a series
+#[derive(Debug)]
+pub struct Backtest {
+  /// Mean absolute error
+  pub mae: f64,
+  /// Mean absolute percentage error, over the held out values that are not zero
+  pub mape: Option<f64>,
+  /// Percentage of held out values that fell within the prediction interval
+  pub coverage: f64,
+}
+
+/// Fit a model on all but the last `holdout` values and compare its forecast against them
+pub fn backtest(series: &[f64], holdout: usize) -> Option<Backtest> {
+  if holdout == 0 || series.len() < holdout + MIN_OBSERVATIONS {
+    return None;
+  }
+
+  let (train, actual) = series.split_at(series.len() - holdout);
+  let forecast = forecast(train, holdout)?;
+
+  let mae = actual
+    .iter()
+    .zip(&forecast.values)
+    .map(|(a, f)| (a - f).abs())
+    .sum::<f64>()
+    / holdout as f64;
+
+  let percentage_errors: Vec<f64> = actual
+    .iter()
+    .zip(&forecast.values)
+    .filter(|(a, _)| **a != 0.0)
+    .map(|(a, f)| ((a - f) / a).abs() * 100.0)
+    .collect();
+  let mape =
+    (!percentage_errors.is_empty()).then(|| percentage_errors.iter().sum::<f64>() / percentage_errors.len() as f64);
+
+  let covered = actual
+    .iter()
+    .enumerate()
+    .filter(|(i, a)| forecast.lower[*i] <= **a && **a <= forecast.upper[*i])
+    .count();
+
+  Some(Backtest {
+    mae,
+    mape,
+    coverage: covered as f64 / holdout as f64 * 100.0,
+  })
+}
+
+/// Chart page that the cumulative registry download forecasts are added to
+const REGISTRY_PAGE: &str = "registry-downloads.json";
+
+/// The monthly series that are forecast: chart page filename, title and the series per module
+pub(crate) fn series(
+  data_path: &Path,
+  versions: &crate::config::VersionConfig,
+  clock: &Clock,
+) -> Result<Vec<(&'static str, &'static str, ModuleSeries)>> {
+  Ok(vec![
+    (
+      REGISTRY_PAGE,
+      "Terraform Registry Downloads per Month",
+      crate::registry::monthly_downloads(data_path, versions, clock)?,
+    ),
+    (
+      "github-views.json",
+      "Repository Page Views",
+      crate::github::monthly_traffic(data_path, "views", clock)?,
+    ),
+    (
+      "github-clones.json",
+      "Repository Clones",
+      crate::github::monthly_traffic(data_path, "clones", clock)?,
+    ),
+  ])
+}
+
+/// Forecast datasets continuing a monthly history, labelled `<label>`, `<label> (lower)` and `<label> (upper)`
+///
+/// With a `base`, the monthly forecasts are added up from it, so they continue a cumulative chart.
+fn datasets(module: &str, label: &str, history: &BTreeMap<NaiveDate, u64>, base: Option<u64>) -> Vec<ChartDataset> {
+  let values: Vec<f64> = history.values().map(|v| *v as f64).collect();
+  let (Some(f), Some(last)) = (forecast(&values, DEFAULT_HORIZON), history.keys().next_back()) else {
+    debug!("Not enough history to forecast {module}");
+    return Vec::new();
+  };
+  let dates: Vec<NaiveDate> = (1..=DEFAULT_HORIZON).map(|h| *last + Months::new(h as u32)).collect();
+
+  [
+    (label.to_string(), ForecastBand::Predicted, f.values),
+    (format!("{label} (lower)"), ForecastBand::Lower, f.lower),
+    (format!("{label} (upper)"), ForecastBand::Upper, f.upper),
+  ]
+  .into_iter()
+  .map(|(label, band, values)| {
+    let values: Vec<f64> = match base {
+      Some(base) => values
+        .iter()
+        .scan(base as f64, |total, v| {
+          *total += v;
+          Some(*total)
+        })
+        .collect(),
+      None => values,
+    };
+    ChartDataset {
+      label,
+      data: dates
+        .iter()
+        .zip(values)
+        .map(|(d, v)| DataPoint {
+          x: d.to_string(),
+          y: v.round() as u64,
+        })
+        .collect(),
+      forecast: Some(band),
+    }
+  })
+  .collect()
+}
+
+/// Add forecast datasets after the history of each module in the monthly registry downloads and traffic pages
+///
+/// The registry sections get the cumulative downloads of all their major versions forecast as `forecast`, and the
+/// traffic sections get a `<module> forecast` for each module.
+pub(crate) fn extend(pages: &mut [(&'static str, ChartPage)], series: Vec<(&'static str, &'static str, ModuleSeries)>) {
+  for (filename, _, modules) in series {
+    let Some((_, page)) = pages.iter_mut().find(|(f, _)| *f == filename) else {
+      continue;
+    };
+
+    for section in &mut page.sections {
+      let forecasts: Vec<ChartDataset> = if filename == REGISTRY_PAGE {
+        let Some(history) = modules.get(&section.title) else {
+          continue;
+        };
+        let base = section.datasets.iter().filter_map(|d| d.data.last()).map(|p| p.y).sum();
+        datasets(&section.title, "forecast", history, Some(base))
+      } else {
+        section
+          .datasets
+          .iter()
+          .filter_map(|d| modules.get(&d.label).map(|history| (&d.label, history)))
+          .flat_map(|(module, history)| datasets(module, &format!("{module} forecast"), history, None))
+          .collect()
+      };
+      section.datasets.extend(forecasts);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_forecast_linear_trend() {
+    let series: Vec<f64> = (0..10).map(|i| 100.0 + 10.0 * i as f64).collect();
+    let f = forecast(&series, 3).unwrap();
+
+    for (h, v) in f.values.iter().enumerate() {
+      let expected = 100.0 + 10.0 * (10 + h) as f64;
+      assert!((v - expected).abs() < 1.0, "expected {expected}, got {v}");
+    }
+    assert!(f.lower.iter().zip(&f.values).all(|(l, v)| l <= v));
+    assert!(f.upper.iter().zip(&f.values).all(|(u, v)| u >= v));
+  }
+
+  #[test]
+  fn test_forecast_seasonal() {
+    let pattern = [0.0, 5.0, 10.0, 20.0, 10.0, 5.0, 0.0, -5.0, -10.0, -20.0, -10.0, -5.0];
+    let series: Vec<f64> = (0..36).map(|i| 500.0 + pattern[i % SEASON]).collect();
+    let f = forecast(&series, SEASON).unwrap();
+
+    for (h, v) in f.values.iter().enumerate() {
+      let expected = 500.0 + pattern[h];
+      assert!((v - expected).abs() < 2.0, "month {h}: expected {expected}, got {v}");
+    }
+  }
+
+  #[test]
+  fn test_forecast_requires_history() {
+    assert!(forecast(&[1.0, 2.0, 3.0], 3).is_none());
+    assert!(backtest(&[1.0, 2.0, 3.0, 4.0, 5.0], 3).is_none());
+  }
+
+  #[test]
+  fn test_extend_pages() {
+    let months = |n: u32| -> BTreeMap<NaiveDate, u64> {
+      (0..n)
+        .map(|i| (NaiveDate::from_ymd_opt(2024, 1, 1).unwrap() + Months::new(i), 100))
+        .collect()
+    };
+    let history = |label: &str, y: u64| ChartDataset {
+      label: label.to_string(),
+      data: vec![DataPoint {
+        x: "2024-06-01".to_string(),
+        y,
+      }],
+      forecast: None,
+    };
+    let page = |title: &str, datasets: Vec<ChartDataset>| ChartPage {
+      schema_version: crate::graph::SCHEMA_VERSION,
+      title: String::new(),
+      updated_at: String::new(),
+      sections: vec![crate::graph::ChartSection {
+        title: title.to_string(),
+        datasets,
+      }],
+    };
+    let mut pages = vec![
+      (
+        REGISTRY_PAGE,
+        page("vpc", vec![history("v4.0", 1_000), history("v5.0", 2_000)]),
+      ),
+      (
+        "github-views.json",
+        page("All", vec![history("vpc", 100), history("eks", 5)]),
+      ),
+    ];
+
+    extend(
+      &mut pages,
+      vec![
+        (REGISTRY_PAGE, "", BTreeMap::from([("vpc".to_string(), months(6))])),
+        (
+          "github-views.json",
+          "",
+          BTreeMap::from([("vpc".to_string(), months(6)), ("eks".to_string(), months(2))]),
+        ),
+      ],
+    );
+
+    // Registry forecasts add the monthly downloads up from the latest total of every major
+    let registry = &pages[0].1.sections[0].datasets;
+    assert_eq!(registry.len(), 5);
+    assert_eq!(
+      (registry[2].label.as_str(), registry[2].forecast),
+      ("forecast", Some(ForecastBand::Predicted))
+    );
+    assert_eq!(registry[2].data[0].x, "2024-07-01");
+    assert_eq!(registry[2].data[0].y, 3_100);
+    assert_eq!(registry[2].data.last().unwrap().y, 3_600);
+
+    // Traffic forecasts follow each module with enough history
+    let labels: Vec<&str> = pages[1].1.sections[0]
+      .datasets
+      .iter()
+      .map(|d| d.label.as_str())
+      .collect();
+    assert_eq!(
+      labels,
+      [
+        "vpc",
+        "eks",
+        "vpc forecast",
+        "vpc forecast (lower)",
+        "vpc forecast (upper)"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_backtest_accuracy() {
+    let series: Vec<f64> = (0..12).map(|i| 1000.0 + 50.0 * i as f64).collect();
+    let result = backtest(&series, 3).unwrap();
+
+    assert!(result.mae < 5.0);
+    assert!(result.mape.unwrap() < 1.0);
+  }
+}
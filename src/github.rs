@@ -1,32 +1,37 @@
 use std::{collections::BTreeMap, env, fs, path::Path};
 
-use anyhow::{Context, Result, bail};
-use chrono::Datelike;
+use chrono::NaiveDate;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use url::Url;
 
-const GITHUB_TOKEN_ENV_VAR: &str = "TERRAFORM_MODULE_DATA";
-const NO_ACCESS: [&str; 1] = ["s3-object"];
+use crate::{
+  clock::Clock,
+  error::{Error, Result},
+  graph::ChartOptions,
+  topics::Categories,
+};
+
+pub(crate) const GITHUB_TOKEN_ENV_VAR: &str = "TERRAFORM_MODULE_DATA";
+pub(crate) const NO_ACCESS: [&str; 1] = ["s3-object"];
 
 /// A single traffic entry (used for both page views and clones)
 #[derive(Debug, Serialize, Deserialize)]
-struct TrafficEntry {
-  count: u64,
-  timestamp: String,
-  uniques: u64,
+pub(crate) struct TrafficEntry {
+  pub(crate) count: u64,
+  pub(crate) timestamp: String,
+  pub(crate) uniques: u64,
 }
 
-type TrafficSummary = BTreeMap<String, TrafficEntry>;
+pub(crate) type TrafficSummary = BTreeMap<String, TrafficEntry>;
 
 /// Load the currently saved traffic data from file
 fn get_current_traffic(path: &Path) -> Result<TrafficSummary> {
   match fs::read_to_string(path) {
     Ok(data) => {
       info!("Reading existing data from file: {}", path.display());
-      let summary: TrafficSummary = serde_json::from_str(&data)?;
-      Ok(summary)
+      serde_json::from_str(&data).map_err(|e| Error::parse(path.display(), e))
     }
     Err(_) => {
       info!("No existing data found for {}, creating new summary", path.display());
@@ -40,8 +45,7 @@ fn summarize_traffic(entries: Vec<TrafficEntry>, path: &Path) -> Result<TrafficS
   let mut summary = get_current_traffic(path)?;
 
   for v in entries {
-    let timestamp = chrono::DateTime::parse_from_rfc3339(&v.timestamp).context("Failed to parse timestamp")?;
-    summary.insert(timestamp.date_naive().to_string(), v);
+    summary.insert(date(&v)?.to_string(), v);
   }
 
   info!("{} summarized", path.display());
@@ -53,58 +57,102 @@ fn summarize_traffic(entries: Vec<TrafficEntry>, path: &Path) -> Result<TrafficS
 fn write_traffic(entries: Vec<TrafficEntry>, dir: &Path, filename: &str) -> Result<()> {
   let filepath = dir.join(filename);
   let summary = summarize_traffic(entries, &filepath)?;
-  std::fs::create_dir_all(dir)?;
+  std::fs::create_dir_all(dir).map_err(|e| Error::storage(dir, e))?;
 
-  let json = serde_json::to_string_pretty(&summary)?;
-  std::fs::write(filepath, json)?;
+  let json = serde_json::to_string_pretty(&summary).map_err(|e| Error::storage(&filepath, e.into()))?;
+  std::fs::write(&filepath, json).map_err(|e| Error::storage(&filepath, e))?;
 
   Ok(())
 }
 
-/// Fetch traffic data (views or clones) from the GitHub API
-async fn get_traffic(module: &str, traffic_type: &str) -> Result<Vec<TrafficEntry>> {
+/// GET a GitHub API endpoint of a module's repository with the configured token
+async fn get_repo(module: &str, endpoint: &str, resource: &str) -> Result<serde_json::Value> {
   if NO_ACCESS.contains(&module) {
-    bail!("No access to {traffic_type} data for {module}");
+    return Err(Error::Auth {
+      resource: resource.to_string(),
+      reason: "the token has no access to the repository".to_string(),
+    });
   }
 
-  let url = Url::parse(
-    format!("https://api.github.com/repos/terraform-aws-modules/terraform-aws-{module}/traffic/{traffic_type}")
-      .as_str(),
-  )?;
+  let url =
+    Url::parse(format!("https://api.github.com/repos/terraform-aws-modules/terraform-aws-{module}{endpoint}").as_str())
+      .map_err(|e| Error::parse(format!("GitHub URL of {module}"), e))?;
 
-  let token = match env::var(GITHUB_TOKEN_ENV_VAR) {
-    Ok(v) => v,
-    Err(e) => bail!("${GITHUB_TOKEN_ENV_VAR} is not set ({})", e),
-  };
+  let token = env::var(GITHUB_TOKEN_ENV_VAR).map_err(|e| Error::Auth {
+    resource: resource.to_string(),
+    reason: format!("${GITHUB_TOKEN_ENV_VAR} is not set ({e})"),
+  })?;
 
-  let resp = Client::builder()
+  let client = Client::builder()
     .user_agent("Module Download Data")
-    .build()?
-    .get(url)
+    .build()
+    .map_err(|e| Error::network(&url, e))?;
+  let resp = client
+    .get(url.clone())
     .header("Accept", "application/vnd.github+json")
     .header("Authorization", format!("Bearer {token}"))
     .header("X-GitHub-Api-Version", "2022-11-28")
     .send()
-    .await?;
-
-  if resp.status().is_success() {
-    let value: serde_json::Value = resp.json().await?;
-    let entries_key = if traffic_type == "views" { "views" } else { "clones" };
-    let entries: Vec<TrafficEntry> = serde_json::from_value(
-      value
-        .get(entries_key)
-        .ok_or_else(|| anyhow::anyhow!("Missing '{entries_key}' field in response"))?
-        .clone(),
-    )?;
-    debug!("GET /traffic/{traffic_type} response: {entries:#?}");
-    Ok(entries)
-  } else {
-    error!("GET /traffic/{traffic_type} response: {resp:#?}");
-    bail!("Failed to get {traffic_type} data")
+    .await
+    .map_err(|e| Error::network(&url, e))?;
+
+  let resp = crate::error::check(&url, resource, resp).inspect_err(|e| error!("GET {url}: {e}"))?;
+  resp.json().await.map_err(|e| Error::network(&url, e))
+}
+
+/// Fetch traffic data (views or clones) from the GitHub API
+async fn get_traffic(module: &str, traffic_type: &str) -> Result<Vec<TrafficEntry>> {
+  let value = get_repo(
+    module,
+    &format!("/traffic/{traffic_type}"),
+    &format!("{traffic_type} of {module}"),
+  )
+  .await?;
+  let entries_key = if traffic_type == "views" { "views" } else { "clones" };
+  let entries = value.get(entries_key).cloned().ok_or_else(|| {
+    Error::parse(
+      format!("{traffic_type} of {module}"),
+      format!("missing '{entries_key}' field"),
+    )
+  })?;
+  let entries: Vec<TrafficEntry> =
+    serde_json::from_value(entries).map_err(|e| Error::parse(format!("{traffic_type} of {module}"), e))?;
+  debug!("GET /traffic/{traffic_type} response: {entries:#?}");
+  Ok(entries)
+}
+
+/// Fetch the topics of a module's repository
+async fn get_topics(module: &str) -> Result<Vec<String>> {
+  let value = get_repo(module, "", &format!("repository of {module}")).await?;
+  let mut topics: Vec<String> = value
+    .get("topics")
+    .cloned()
+    .map(serde_json::from_value)
+    .transpose()
+    .map_err(|e| Error::parse(format!("topics of {module}"), e))?
+    .unwrap_or_default();
+  topics.sort();
+  Ok(topics)
+}
+
+/// Topics saved for a module, empty when none have been collected
+pub(crate) fn load_topics(data_path: &Path, module: &str) -> Result<Vec<String>> {
+  let filepath = data_path.join("github").join(module).join("topics.json");
+  match fs::read_to_string(&filepath) {
+    Ok(data) => serde_json::from_str(&data).map_err(|e| Error::parse(filepath.display(), e)),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+    Err(e) => Err(Error::storage(&filepath, e)),
   }
 }
 
-/// Collect module traffic data from GitHub
+/// Date of a traffic entry
+fn date(entry: &TrafficEntry) -> Result<NaiveDate> {
+  chrono::DateTime::parse_from_rfc3339(&entry.timestamp)
+    .map(|ts| ts.date_naive())
+    .map_err(|e| Error::parse(format!("traffic timestamp {}", entry.timestamp), e))
+}
+
+/// Collect module traffic data and repository topics from GitHub
 pub async fn collect(path: &Path, module: &str) -> Result<()> {
   let gh_path = path.join("github").join(module.to_lowercase());
 
@@ -114,131 +162,128 @@ pub async fn collect(path: &Path, module: &str) -> Result<()> {
   let clones = get_traffic(module, "clones").await?;
   write_traffic(clones, &gh_path, "clones.json")?;
 
+  // Topics only refine the categories, so a failed request keeps the previous topics instead of failing the module
+  match get_topics(module).await {
+    Ok(topics) => {
+      let filepath = gh_path.join("topics.json");
+      let json = serde_json::to_string_pretty(&topics).map_err(|e| Error::storage(&filepath, e.into()))?;
+      fs::write(&filepath, json).map_err(|e| Error::storage(&filepath, e))?;
+    }
+    Err(e) => warn!("Keeping the previous topics of {module}: {e}"),
+  }
+
   Ok(())
 }
 
-/// Output JSON data for the Astro site
-pub(crate) fn graph(data_path: &Path, output_path: &Path) -> Result<()> {
-  let timestamp = chrono::Local::now().to_utc().format("%Y-%m-%d %H:%M:%S").to_string();
-
-  write_traffic_json(
-    &timestamp,
-    data_path,
-    output_path,
-    "Repository Clones",
-    "clones",
-    "github-clones.json",
-  )?;
-  write_traffic_json(
-    &timestamp,
-    data_path,
-    output_path,
-    "Repository Page Views",
-    "views",
-    "github-views.json",
-  )?;
-
-  Ok(())
+/// Chart pages for the repository clones and page views, keyed by output filename
+pub(crate) fn pages(
+  data_path: &Path,
+  timestamp: &str,
+  options: &ChartOptions,
+  categories: &Categories,
+  clock: &Clock,
+) -> Result<Vec<(&'static str, crate::graph::ChartPage)>> {
+  Ok(vec![
+    (
+      "github-clones.json",
+      traffic_page(
+        timestamp,
+        data_path,
+        "Repository Clones",
+        "clones",
+        options,
+        categories,
+        clock,
+      )?,
+    ),
+    (
+      "github-views.json",
+      traffic_page(
+        timestamp,
+        data_path,
+        "Repository Page Views",
+        "views",
+        options,
+        categories,
+        clock,
+      )?,
+    ),
+  ])
 }
 
-fn write_traffic_json(
+fn traffic_page(
   timestamp: &str,
   data_path: &Path,
-  output_path: &Path,
   title: &str,
   data_type: &str,
-  filename: &str,
-) -> Result<()> {
-  let category_names: &[(&str, Option<&str>)] = &[
-    ("All", None),
-    ("Compute", Some(crate::COMPUTE)),
-    ("Serverless", Some(crate::SERVERLESS)),
-    ("Data", Some(crate::DATA)),
-    ("Networking", Some(crate::NETWORKING)),
-    ("Other", Some(crate::OTHER)),
-  ];
-
-  let mut sections = Vec::new();
-  for &(section_title, category) in category_names {
-    let datasets = collect_traffic_datasets(category, data_type, data_path)?;
-    sections.push(crate::graph::ChartSection {
-      title: section_title.to_string(),
-      datasets,
-    });
-  }
-
-  let page = crate::graph::ChartPage {
+  options: &ChartOptions,
+  categories: &Categories,
+  clock: &Clock,
+) -> Result<crate::graph::ChartPage> {
+  let buckets = traffic(data_path, data_type, options, clock)?;
+
+  Ok(crate::graph::ChartPage {
+    schema_version: crate::graph::SCHEMA_VERSION,
     title: title.to_string(),
     updated_at: timestamp.to_string(),
-    sections,
-  };
+    sections: crate::graph::category_sections(&buckets, categories)?,
+  })
+}
+
+/// Module names that have GitHub traffic data collected, sorted by name
+pub(crate) fn modules(data_path: &Path) -> Result<Vec<String>> {
+  let mut modules = Vec::new();
+  let path = data_path.join("github");
+  for entry in fs::read_dir(&path).map_err(|e| Error::storage(&path, e))? {
+    let entry = entry.map_err(|e| Error::storage(&path, e))?;
+    modules.push(crate::file_stem(&entry.path())?.to_owned());
+  }
+  modules.sort();
 
-  info!("Writing {filename}");
-  crate::graph::write_chart_page(output_path, filename, &page)
+  Ok(modules)
 }
 
-fn collect_traffic_datasets(
-  category: Option<&str>,
-  data_type: &str,
-  data_path: &Path,
-) -> Result<Vec<crate::graph::ChartDataset>> {
-  let mut datasets = Vec::new();
-
-  for entry in fs::read_dir(data_path.join("github"))? {
-    let entry = entry?;
-    let dir_name = entry
-      .path()
-      .file_stem()
-      .ok_or_else(|| anyhow::anyhow!("Missing file stem for path: {:?}", entry.path()))?
-      .to_str()
-      .ok_or_else(|| anyhow::anyhow!("Non-UTF8 file stem for path: {:?}", entry.path()))?
-      .to_owned();
-    let filepath = entry.path().join(format!("{data_type}.json"));
-
-    // If directory is not in category, skip; if no category provided, return all
-    if let Some(c) = category
-      && !crate::CATEGORIES
-        .get(c)
-        .ok_or_else(|| anyhow::anyhow!("Unknown category: {c}"))?
-        .contains(&dir_name.as_str())
-    {
-      continue;
-    }
+/// Load the saved traffic data (`views` or `clones`) for a module
+pub(crate) fn load_traffic(data_path: &Path, module: &str, data_type: &str) -> Result<TrafficSummary> {
+  let filepath = data_path.join("github").join(module).join(format!("{data_type}.json"));
+  let data = fs::read_to_string(&filepath).map_err(|e| Error::storage(&filepath, e))?;
+  serde_json::from_str(&data).map_err(|e| Error::parse(filepath.display(), e))
+}
 
-    let data = fs::read_to_string(filepath)?;
-    let summary: TrafficSummary = serde_json::from_str(&data)?;
-
-    // Aggregate daily data into monthly buckets (sum counts per month)
-    let mut monthly: BTreeMap<chrono::NaiveDate, u64> = BTreeMap::new();
-    for v in summary.values() {
-      let ts = chrono::DateTime::parse_from_rfc3339(&v.timestamp).context("Failed to parse timestamp")?;
-      let date = ts.date_naive();
-      let month_start = chrono::NaiveDate::from_ymd_opt(date.year(), date.month(), 1)
-        .ok_or_else(|| anyhow::anyhow!("Invalid date: {date}"))?;
-      *monthly.entry(month_start).or_insert(0) += v.count;
-    }
+/// Daily traffic counts for a module, keyed by date
+pub(crate) fn daily_traffic(data_path: &Path, module: &str, data_type: &str) -> Result<BTreeMap<NaiveDate, u64>> {
+  let summary = load_traffic(data_path, module, data_type)?;
 
-    let dates: Vec<chrono::NaiveDate> = monthly.keys().copied().collect();
-    let values: Vec<u64> = monthly.values().copied().collect();
-    let (dates, values) = crate::graph::filter_incomplete_month(dates, values);
-
-    let data_points = dates
-      .iter()
-      .zip(values.iter())
-      .map(|(d, v)| crate::graph::DataPoint {
-        x: d.to_string(),
-        y: *v,
-      })
-      .collect();
-
-    datasets.push(crate::graph::ChartDataset {
-      label: dir_name,
-      data: data_points,
-    });
+  let mut daily = BTreeMap::new();
+  for v in summary.values() {
+    daily.insert(date(v)?, v.count);
   }
 
-  // Sort datasets by label for consistent output
-  datasets.sort_by(|a, b| a.label.cmp(&b.label));
+  Ok(daily)
+}
+
+/// Traffic counts per module aggregated into complete monthly buckets (sum of counts per month)
+pub(crate) fn monthly_traffic(
+  data_path: &Path,
+  data_type: &str,
+  clock: &Clock,
+) -> Result<BTreeMap<String, BTreeMap<NaiveDate, u64>>> {
+  traffic(data_path, data_type, &ChartOptions::default(), clock)
+}
+
+/// Traffic counts per module summed into the complete buckets of the chart options
+pub(crate) fn traffic(
+  data_path: &Path,
+  data_type: &str,
+  options: &ChartOptions,
+  clock: &Clock,
+) -> Result<BTreeMap<String, BTreeMap<NaiveDate, u64>>> {
+  let mut traffic = BTreeMap::new();
+
+  for module in modules(data_path)? {
+    let buckets = options.complete_buckets(&daily_traffic(data_path, &module, data_type)?, false, clock);
+    traffic.insert(module, buckets);
+  }
 
-  Ok(datasets)
+  Ok(traffic)
 }
@@ -1,58 +1,359 @@
-use std::{fs, path::Path};
+use std::{collections::BTreeMap, fs, path::Path};
 
 use anyhow::Result;
 use chrono::{Datelike, NaiveDate};
-use serde::Serialize;
+use clap::ValueEnum;
+use schemars::{JsonSchema, Schema, schema_for};
+use serde::{Deserialize, Serialize};
+use tracing::info;
 
-#[derive(Debug, Serialize)]
+use crate::{
+  clock::Clock,
+  store::{Aggregation, aggregate},
+  topics::Categories,
+};
+
+/// Version of the JSON contract of the output pages
+///
+/// Incremented once per published change to the contract: a field added, removed, renamed or retyped on any page,
+/// or a page shape added or removed.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A single series of a chart, such as one module or one major version
+#[derive(Clone, Debug, Serialize, JsonSchema)]
 pub struct ChartDataset {
   pub label: String,
   pub data: Vec<DataPoint>,
+  /// Part of a forecast the dataset is; absent on historical data
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub forecast: Option<ForecastBand>,
 }
 
-#[derive(Debug, Serialize)]
+/// Forecast datasets: the predicted values and the bounds of their 95% prediction interval
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ForecastBand {
+  Predicted,
+  Lower,
+  Upper,
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
 pub struct DataPoint {
+  /// Date (YYYY-MM-DD) or category of the point
   pub x: String,
   pub y: u64,
 }
 
-#[derive(Debug, Serialize)]
+/// A page of charts written to `site/public/data/<page>.json`
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct ChartPage {
+  /// Version of the JSON contract, see `schemas/chart-page.schema.json`
+  pub schema_version: u32,
   pub title: String,
+  /// UTC time the page was generated, as `YYYY-MM-DD HH:MM:SS`
   pub updated_at: String,
   pub sections: Vec<ChartSection>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct ChartSection {
   pub title: String,
   pub datasets: Vec<ChartDataset>,
 }
 
-pub fn graph(data_path: &Path) -> Result<()> {
-  let output_path = Path::new("site").join("public").join("data");
-  fs::create_dir_all(&output_path)?;
+/// Size of the time buckets that data points are grouped into
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+  Day,
+  /// ISO 8601 weeks, starting on Monday
+  Week,
+  #[default]
+  Month,
+  Quarter,
+}
+
+impl Granularity {
+  /// First day of the bucket that contains a date
+  pub fn bucket(self, date: NaiveDate) -> NaiveDate {
+    match self {
+      Self::Day => date,
+      Self::Week => date - chrono::Days::new(date.weekday().num_days_from_monday() as u64),
+      Self::Month => date.with_day(1).unwrap_or(date),
+      Self::Quarter => NaiveDate::from_ymd_opt(date.year(), date.month0() / 3 * 3 + 1, 1).unwrap_or(date),
+    }
+  }
+}
+
+/// Bucket size and date range of the registry and traffic charts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChartOptions {
+  pub granularity: Granularity,
+  /// First day included in the charts
+  pub from: Option<NaiveDate>,
+  /// Last day included in the charts
+  pub to: Option<NaiveDate>,
+}
+
+impl ChartOptions {
+  /// Group a daily series into buckets within the date range
+  ///
+  /// Cumulative series keep the last value in each bucket, other series are summed.
+  pub fn buckets(&self, daily: &BTreeMap<NaiveDate, u64>, cumulative: bool) -> BTreeMap<NaiveDate, u64> {
+    let aggregation = if cumulative {
+      Aggregation::Last
+    } else {
+      Aggregation::Sum
+    };
+    let from = self.from.unwrap_or(NaiveDate::MIN);
+    let to = self.to.unwrap_or(NaiveDate::MAX);
+    if from > to {
+      return BTreeMap::new();
+    }
+    aggregate(
+      daily.range(from..=to).map(|(d, v)| (*d, *v)),
+      self.granularity,
+      aggregation,
+    )
+  }
+
+  /// Buckets of a daily series without incomplete buckets: the final bucket while it contains today or ends after
+  /// `to`, and the first bucket when it starts before `from`
+  pub fn complete_buckets(
+    &self,
+    daily: &BTreeMap<NaiveDate, u64>,
+    cumulative: bool,
+    clock: &Clock,
+  ) -> BTreeMap<NaiveDate, u64> {
+    let today = clock.today();
+    let end = self.to.and_then(|t| t.succ_opt()).map_or(today, |t| t.min(today));
+
+    let buckets = self.buckets(daily, cumulative);
+    let (dates, values) = filter_incomplete_period(
+      buckets.keys().copied().collect(),
+      buckets.values().copied().collect(),
+      self.granularity,
+      end,
+    );
+    dates
+      .into_iter()
+      .zip(values)
+      .filter(|(date, _)| self.from.is_none_or(|f| *date >= f))
+      .collect()
+  }
+}
+
+/// Output format of the generated graphs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+  /// Chart JSON for the Astro site
+  #[default]
+  Json,
+  /// One SVG image per chart section
+  Svg,
+  /// One PNG image per chart section (requires the `png` feature)
+  Png,
+}
 
-  crate::github::graph(data_path, &output_path)?;
-  crate::registry::graph(data_path, &output_path)?;
+pub fn graph(
+  data_path: &Path,
+  format: Format,
+  options: &ChartOptions,
+  clock: &Clock,
+  config: &crate::config::Config,
+) -> Result<()> {
+  let categories = Categories::load(data_path, &config.categories)?;
+  match format {
+    Format::Json => write_json(
+      data_path,
+      &Path::new("site").join("public").join("data"),
+      options,
+      clock,
+      config,
+      &categories,
+    )?,
+    Format::Svg | Format::Png => {
+      let output_path = Path::new("site").join("public").join("charts");
+      for (filename, page) in pages(data_path, options, &config.versions, &categories, clock)? {
+        let page_path = output_path.join(filename.trim_end_matches(".json"));
+        fs::create_dir_all(&page_path)?;
+
+        info!("Writing charts for {filename}");
+        for (name, svg) in crate::svg::page(&page) {
+          write_image(&page_path, &name, &svg, format)?;
+        }
+      }
+    }
+  }
 
   Ok(())
 }
 
-/// Filter out the current (incomplete) month from date/value pairs
-pub fn filter_incomplete_month(dates: Vec<NaiveDate>, values: Vec<u64>) -> (Vec<NaiveDate>, Vec<u64>) {
-  let today = chrono::Local::now().date_naive();
-  let current_month_start = NaiveDate::from_ymd_opt(today.year(), today.month(), 1);
-
-  match current_month_start {
-    Some(cutoff) => {
-      let (d, v): (Vec<_>, Vec<_>) = dates.into_iter().zip(values).filter(|(date, _)| *date < cutoff).unzip();
-      (d, v)
+/// JSON Schemas of the output pages, keyed by the filename they are written to
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+  vec![
+    ("chart-page.schema.json", schema_for!(ChartPage)),
+    ("leaderboard.schema.json", schema_for!(crate::leaderboard::Leaderboard)),
+    ("badge.schema.json", schema_for!(crate::badges::Badge)),
+    ("module.schema.json", schema_for!(crate::detail::ModuleDetail)),
+    ("index.schema.json", schema_for!(crate::detail::ModuleIndex)),
+    ("organization.schema.json", schema_for!(crate::totals::Organization)),
+  ]
+}
+
+fn write_json(
+  data_path: &Path,
+  output_path: &Path,
+  options: &ChartOptions,
+  clock: &Clock,
+  config: &crate::config::Config,
+  categories: &Categories,
+) -> Result<()> {
+  fs::create_dir_all(output_path)?;
+
+  let pages = pages(data_path, options, &config.versions, categories, clock)?;
+  for (filename, page) in &pages {
+    info!("Writing {filename}");
+    write_chart_page(output_path, filename, page)?;
+  }
+  crate::detail::graph(&pages, output_path, categories)?;
+  crate::leaderboard::graph(data_path, output_path, categories, &config.versions, clock)?;
+  crate::totals::graph(data_path, output_path, &config.versions, categories, clock)?;
+  crate::badges::graph(data_path, output_path, clock, &config.badges, &config.versions)?;
+
+  let schema_path = output_path.join("schemas");
+  fs::create_dir_all(&schema_path)?;
+  for (filename, schema) in schemas() {
+    fs::write(schema_path.join(filename), serde_json::to_string_pretty(&schema)?)?;
+  }
+
+  Ok(())
+}
+
+fn write_image(path: &Path, name: &str, svg: &str, format: Format) -> Result<()> {
+  match format {
+    #[cfg(feature = "png")]
+    Format::Png => fs::write(path.join(format!("{name}.png")), crate::svg::png(svg)?)?,
+    #[cfg(not(feature = "png"))]
+    Format::Png => anyhow::bail!("PNG output requires tmd to be built with the `png` feature"),
+    _ => fs::write(path.join(format!("{name}.svg")), svg)?,
+  }
+  Ok(())
+}
+
+/// Build every chart page from the collected data, keyed by the filename it is written to
+///
+/// The options apply to the registry and traffic pages, which also get forecast datasets when they are charted by
+/// month up to the latest complete month; adoption is always monthly. The version rules decide which major versions
+/// the registry downloads page shows, and the categories how modules are grouped.
+pub fn pages(
+  data_path: &Path,
+  options: &ChartOptions,
+  versions: &crate::config::VersionConfig,
+  categories: &Categories,
+  clock: &Clock,
+) -> Result<Vec<(String, ChartPage)>> {
+  let timestamp = clock.timestamp();
+
+  let mut pages = crate::github::pages(data_path, &timestamp, options, categories, clock)?;
+  pages.push((
+    "registry-downloads.json",
+    crate::registry::page(data_path, &timestamp, options, versions, clock)?,
+  ));
+  pages.push((
+    "registry-categories.json",
+    crate::registry::category_page(data_path, &timestamp, options, versions, categories, clock)?,
+  ));
+  pages.push((
+    "category-totals.json",
+    crate::totals::page(data_path, &timestamp, options, versions, categories, clock)?,
+  ));
+  pages.push((
+    "major-adoption.json",
+    crate::adoption::page(data_path, &timestamp, versions, clock)?,
+  ));
+  // Forecasts are monthly and continue from the latest complete month, so they only fit charts of the same months
+  if options.granularity == Granularity::Month && options.to.is_none() {
+    crate::forecast::extend(&mut pages, crate::forecast::series(data_path, versions, clock)?);
+  }
+
+  Ok(pages.into_iter().map(|(f, p)| (f.to_string(), p)).collect())
+}
+
+/// One section per category (plus `All`) with a dataset for each module in it
+pub(crate) fn category_sections(
+  series: &BTreeMap<String, BTreeMap<NaiveDate, u64>>,
+  categories: &Categories,
+) -> crate::error::Result<Vec<ChartSection>> {
+  let mut sections = Vec::new();
+  for (title, category) in crate::CATEGORY_SECTIONS {
+    let mut datasets = Vec::new();
+    for (module, values) in series {
+      // If module is not in category, skip; if no category provided, return all
+      if !categories.contains(category, module)? {
+        continue;
+      }
+
+      datasets.push(ChartDataset {
+        label: module.clone(),
+        data: values
+          .iter()
+          .map(|(d, v)| DataPoint {
+            x: d.to_string(),
+            y: *v,
+          })
+          .collect(),
+        forecast: None,
+      });
     }
-    None => (dates, values),
+
+    sections.push(ChartSection {
+      title: title.to_string(),
+      datasets,
+    });
+  }
+
+  Ok(sections)
+}
+
+/// Whether a dataset of a category section belongs to a module: the module's history or one of its forecasts
+pub(crate) fn is_module(dataset: &ChartDataset, module: &str) -> bool {
+  match dataset.forecast {
+    None => dataset.label == module,
+    Some(_) => dataset
+      .label
+      .strip_prefix(module)
+      .is_some_and(|rest| rest.starts_with(" forecast")),
   }
 }
 
+/// Find a chart page by the filename it is written to
+pub(crate) fn find_page<'a>(pages: &'a [(String, ChartPage)], filename: &str) -> Result<&'a ChartPage> {
+  pages
+    .iter()
+    .find(|(f, _)| f == filename)
+    .map(|(_, p)| p)
+    .ok_or_else(|| anyhow::anyhow!("Missing chart page: {filename}"))
+}
+
+/// Filter out the current (incomplete) month from date/value pairs
+pub fn filter_incomplete_month(dates: Vec<NaiveDate>, values: Vec<u64>, clock: &Clock) -> (Vec<NaiveDate>, Vec<u64>) {
+  filter_incomplete_period(dates, values, Granularity::Month, clock.today())
+}
+
+/// Filter out the buckets that are incomplete as of `end`, the first day without data: the bucket containing `end`
+/// and any later ones
+pub fn filter_incomplete_period(
+  dates: Vec<NaiveDate>,
+  values: Vec<u64>,
+  granularity: Granularity,
+  end: NaiveDate,
+) -> (Vec<NaiveDate>, Vec<u64>) {
+  let cutoff = granularity.bucket(end);
+  dates.into_iter().zip(values).filter(|(date, _)| *date < cutoff).unzip()
+}
+
 pub fn write_chart_page(path: &Path, filename: &str, page: &ChartPage) -> Result<()> {
   let json = serde_json::to_string_pretty(page)?;
   fs::write(path.join(filename), json)?;
@@ -65,19 +366,178 @@ mod tests {
 
   #[test]
   fn test_filter_incomplete_month_removes_current() {
-    let today = chrono::Local::now().date_naive();
-    let current_month = NaiveDate::from_ymd_opt(today.year(), today.month(), 1).unwrap();
+    let clock = Clock::as_of(NaiveDate::from_ymd_opt(2024, 3, 15));
+    let current_month = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
     let last_month = current_month - chrono::Months::new(1);
 
     let dates = vec![last_month, current_month];
     let values = vec![100, 50];
 
-    let (filtered_dates, filtered_values) = filter_incomplete_month(dates, values);
+    let (filtered_dates, filtered_values) = filter_incomplete_month(dates, values, &clock);
     assert_eq!(filtered_dates.len(), 1);
     assert_eq!(filtered_dates[0], last_month);
     assert_eq!(filtered_values[0], 100);
   }
 
+  /// Collected data for two modules over three complete months
+  fn write_fixture(path: &Path) {
+    for (module, scale) in [("vpc", 10), ("eks", 3)] {
+      let registry = path.join("registry").join(module);
+      let github = path.join("github").join(module);
+      fs::create_dir_all(&registry).unwrap();
+      fs::create_dir_all(&github).unwrap();
+
+      let mut traffic = serde_json::Map::new();
+      let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+      for (day, date) in start.iter_days().take(91).enumerate() {
+        let day = day as u64;
+        let summary = serde_json::json!([
+          { "downloads": 1_000 + day * scale, "major_version": "20", "created_at": "2023-06-01" },
+          { "downloads": day * day * scale, "major_version": "21", "created_at": "2024-01-01" },
+        ]);
+        fs::write(registry.join(format!("{date}.json")), summary.to_string()).unwrap();
+        traffic.insert(
+          date.to_string(),
+          serde_json::json!({ "count": day % 7 + scale, "timestamp": format!("{date}T00:00:00Z"), "uniques": 1 }),
+        );
+      }
+      let traffic = serde_json::Value::Object(traffic).to_string();
+      fs::write(github.join("views.json"), &traffic).unwrap();
+      fs::write(github.join("clones.json"), &traffic).unwrap();
+    }
+  }
+
+  #[test]
+  fn test_outputs_match_schemas() {
+    let dir = std::env::temp_dir().join(format!("tmd-schema-{}", std::process::id()));
+    let (data_path, output_path) = (dir.join("data"), dir.join("site"));
+    write_fixture(&data_path);
+    write_json(
+      &data_path,
+      &output_path,
+      &ChartOptions::default(),
+      &Clock::as_of(NaiveDate::from_ymd_opt(2024, 4, 2)),
+      &crate::config::Config::default(),
+      &Categories::builtin(),
+    )
+    .unwrap();
+
+    let read = |path: &Path| -> serde_json::Value { serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap() };
+    let validator = |name: &str| jsonschema::validator_for(&read(&output_path.join("schemas").join(name))).unwrap();
+    let (chart_page, leaderboard, badge, module, index, organization) = (
+      validator("chart-page.schema.json"),
+      validator("leaderboard.schema.json"),
+      validator("badge.schema.json"),
+      validator("module.schema.json"),
+      validator("index.schema.json"),
+      validator("organization.schema.json"),
+    );
+
+    let mut pending = vec![output_path.clone()];
+    let mut validated = 0;
+    while let Some(path) = pending.pop() {
+      for entry in fs::read_dir(&path).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+          if !path.ends_with("schemas") {
+            pending.push(path);
+          }
+          continue;
+        }
+
+        let json = read(&path);
+        let validator = if path.parent().unwrap().parent().unwrap().ends_with("badges") {
+          &badge
+        } else if path.parent().unwrap().ends_with("modules") {
+          &module
+        } else if path.ends_with("index.json") {
+          &index
+        } else if path.ends_with("leaderboard.json") {
+          &leaderboard
+        } else if path.ends_with("organization.json") {
+          &organization
+        } else {
+          assert_eq!(json["schema_version"], SCHEMA_VERSION);
+          &chart_page
+        };
+        let errors: Vec<String> = validator.iter_errors(&json).map(|e| e.to_string()).collect();
+        assert!(
+          errors.is_empty(),
+          "{} does not match its schema: {errors:?}",
+          path.display()
+        );
+        validated += 1;
+      }
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+    // 9 chart pages, the leaderboard, the organization page, the index, and a detail page and 4 badges for each module
+    assert_eq!(validated, 19);
+  }
+
+  #[test]
+  fn test_complete_buckets() {
+    let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+    let daily: BTreeMap<NaiveDate, u64> = date(1, 1).iter_days().take(152).map(|d| (d, 1)).collect();
+    let clock = Clock::as_of(Some(date(12, 31)));
+
+    // Only the months before the clock's current month are complete
+    let april = Clock::as_of(Some(date(4, 10)));
+    let months = ChartOptions::default().complete_buckets(&daily, false, &april);
+    assert_eq!(
+      months.keys().copied().collect::<Vec<_>>(),
+      vec![date(1, 1), date(2, 1), date(3, 1)]
+    );
+
+    // Q1 starts before `from` and Q2 ends after `to`
+    let options = ChartOptions {
+      granularity: Granularity::Quarter,
+      from: Some(date(2, 1)),
+      to: Some(date(5, 15)),
+    };
+    assert!(options.complete_buckets(&daily, false, &clock).is_empty());
+
+    let options = ChartOptions { from: None, ..options };
+    assert_eq!(
+      options.complete_buckets(&daily, false, &clock),
+      BTreeMap::from([(date(1, 1), 91)])
+    );
+    assert_eq!(
+      options.complete_buckets(&daily, true, &clock),
+      BTreeMap::from([(date(1, 1), 1)])
+    );
+
+    // 2024-03-31 is a Sunday, so the week starting 2024-03-25 is complete; the week of 2024-03-11 is partial
+    let options = ChartOptions {
+      granularity: Granularity::Week,
+      from: Some(date(3, 12)),
+      to: Some(date(4, 3)),
+    };
+    assert_eq!(
+      options.complete_buckets(&daily, false, &clock),
+      BTreeMap::from([(date(3, 18), 7), (date(3, 25), 7)])
+    );
+  }
+
+  #[test]
+  fn test_granularity_bucket() {
+    // 2024-01-03 is a Wednesday
+    let date = NaiveDate::from_ymd_opt(2024, 1, 3).unwrap();
+    assert_eq!(Granularity::Day.bucket(date), date);
+    assert_eq!(
+      Granularity::Week.bucket(date),
+      NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()
+    );
+    assert_eq!(
+      Granularity::Month.bucket(NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()),
+      NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()
+    );
+    assert_eq!(
+      Granularity::Quarter.bucket(NaiveDate::from_ymd_opt(2024, 6, 30).unwrap()),
+      NaiveDate::from_ymd_opt(2024, 4, 1).unwrap()
+    );
+  }
+
   #[test]
   fn test_filter_incomplete_month_keeps_complete() {
     let dates = vec![
@@ -86,7 +546,8 @@ mod tests {
     ];
     let values = vec![100, 200];
 
-    let (filtered_dates, filtered_values) = filter_incomplete_month(dates, values);
+    let clock = Clock::as_of(NaiveDate::from_ymd_opt(2024, 3, 1));
+    let (filtered_dates, filtered_values) = filter_incomplete_month(dates, values, &clock);
     assert_eq!(filtered_dates.len(), 2);
     assert_eq!(filtered_values, vec![100, 200]);
   }
@@ -0,0 +1,230 @@
+use std::{collections::BTreeSet, fmt::Write as _, fs, path::Path};
+
+use anyhow::Result;
+use tracing::info;
+
+use crate::{
+  graph::{ChartDataset, ChartPage, ChartSection},
+  svg::escape,
+};
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:0 auto;max-width:960px;padding:1rem;color:#222}\
+nav a{margin-right:1rem}table{border-collapse:collapse;font-size:.85rem}th,td{border:1px solid #ddd;\
+padding:.2rem .5rem;text-align:right}th:first-child,td:first-child{text-align:left}.scroll{overflow-x:auto}\
+svg{width:100%;height:auto}.meta{color:#666;font-size:.85rem}";
+
+/// Wrap page content in a complete HTML document with the shared navigation
+fn document(title: &str, root: &str, updated_at: &str, body: &str) -> String {
+  let mut nav = format!(r#"<a href="{root}index.html">Overview</a>"#);
+  for (section, _) in crate::CATEGORY_SECTIONS.iter().skip(1) {
+    let _ = write!(
+      nav,
+      r#"<a href="{root}categories/{}.html">{section}</a>"#,
+      section.to_lowercase()
+    );
+  }
+
+  format!(
+    "<!DOCTYPE html>\n<html lang=\"en\"><head><meta charset=\"utf-8\"><title>{0}</title><style>{STYLE}</style></head>\
+     <body><nav>{nav}</nav><h1>{0}</h1><p class=\"meta\">Updated {updated_at} UTC</p>{body}</body></html>\n",
+    escape(title)
+  )
+}
+
+/// Chart and data table for a set of datasets, one table row per x value
+fn chart(title: &str, datasets: Vec<&ChartDataset>) -> String {
+  let section = ChartSection {
+    title: title.to_string(),
+    datasets: datasets.into_iter().cloned().collect(),
+  };
+  let xs: BTreeSet<&str> = section
+    .datasets
+    .iter()
+    .flat_map(|d| d.data.iter().map(|p| p.x.as_str()))
+    .collect();
+
+  let mut html = format!("<h2>{}</h2>{}", escape(title), crate::svg::section(&section));
+  html.push_str("<details><summary>Data</summary><div class=\"scroll\"><table><tr><th>Date</th>");
+  for d in &section.datasets {
+    let _ = write!(html, "<th>{}</th>", escape(&d.label));
+  }
+  html.push_str("</tr>");
+  for x in xs {
+    let _ = write!(html, "<tr><td>{x}</td>");
+    for d in &section.datasets {
+      let value = d.data.iter().find(|p| p.x == x).map(|p| p.y.to_string());
+      let _ = write!(html, "<td>{}</td>", value.unwrap_or_default());
+    }
+    html.push_str("</tr>");
+  }
+  html.push_str("</table></div></details>");
+
+  html
+}
+
+fn section<'a>(page: &'a ChartPage, title: &str) -> Option<&'a ChartSection> {
+  page.sections.iter().find(|s| s.title == title)
+}
+
+fn by_label<'a>(datasets: &'a [ChartDataset], module: &str) -> Vec<&'a ChartDataset> {
+  datasets.iter().filter(|d| crate::graph::is_module(d, module)).collect()
+}
+
+/// Sum of the latest value of each historical dataset
+fn latest(datasets: &[&ChartDataset]) -> u64 {
+  datasets
+    .iter()
+    .filter(|d| d.forecast.is_none())
+    .filter_map(|d| d.data.last())
+    .map(|p| p.y)
+    .sum()
+}
+
+/// Render a self-contained static site (overview, category and module pages) from the chart pages
+pub(crate) fn render(pages: &[(String, ChartPage)], output_path: &Path) -> Result<()> {
+  let views = crate::graph::find_page(pages, "github-views.json")?;
+  let clones = crate::graph::find_page(pages, "github-clones.json")?;
+  let registry = crate::graph::find_page(pages, "registry-downloads.json")?;
+
+  let all_views = section(views, "All").map(|s| s.datasets.as_slice()).unwrap_or_default();
+  let all_clones = section(clones, "All")
+    .map(|s| s.datasets.as_slice())
+    .unwrap_or_default();
+
+  let modules: BTreeSet<&str> = registry
+    .sections
+    .iter()
+    .map(|s| s.title.as_str())
+    .chain(all_views.iter().map(|d| d.label.as_str()))
+    .collect();
+
+  // Module pages
+  fs::create_dir_all(output_path.join("modules"))?;
+  for module in &modules {
+    let mut body = String::new();
+    if let Some(s) = section(registry, module) {
+      body.push_str(&chart(&registry.title, s.datasets.iter().collect()));
+    }
+    body.push_str(&chart(&views.title, by_label(all_views, module)));
+    body.push_str(&chart(&clones.title, by_label(all_clones, module)));
+
+    let html = document(&format!("terraform-aws-{module}"), "../", &views.updated_at, &body);
+    fs::write(output_path.join("modules").join(format!("{module}.html")), html)?;
+  }
+
+  // Category pages
+  fs::create_dir_all(output_path.join("categories"))?;
+  for (title, _) in crate::CATEGORY_SECTIONS.iter().skip(1) {
+    let mut body = String::new();
+    for page in [views, clones] {
+      if let Some(s) = section(page, title) {
+        body.push_str(&chart(&page.title, s.datasets.iter().collect()));
+      }
+    }
+
+    let html = document(title, "../", &views.updated_at, &body);
+    fs::write(
+      output_path
+        .join("categories")
+        .join(format!("{}.html", title.to_lowercase())),
+      html,
+    )?;
+  }
+
+  // Overview with the latest month of every module
+  let mut body = String::from("<table><tr><th>Module</th><th>Downloads (total)</th><th>Views</th><th>Clones</th></tr>");
+  for module in &modules {
+    let downloads = section(registry, module)
+      .map(|s| latest(&s.datasets.iter().collect::<Vec<_>>()))
+      .unwrap_or_default();
+    let _ = write!(
+      body,
+      r#"<tr><td><a href="modules/{0}.html">{0}</a></td><td>{downloads}</td><td>{1}</td><td>{2}</td></tr>"#,
+      escape(module),
+      latest(&by_label(all_views, module)),
+      latest(&by_label(all_clones, module)),
+    );
+  }
+  body.push_str("</table>");
+  fs::write(
+    output_path.join("index.html"),
+    document("Terraform Module Data", "", &views.updated_at, &body),
+  )?;
+
+  info!("Wrote {} module pages to {}", modules.len(), output_path.display());
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::graph::DataPoint;
+
+  fn dataset(label: &str, y: u64) -> ChartDataset {
+    ChartDataset {
+      label: label.to_string(),
+      data: vec![DataPoint {
+        x: "2024-01-01".to_string(),
+        y,
+      }],
+      forecast: None,
+    }
+  }
+
+  fn page(title: &str, sections: Vec<ChartSection>) -> ChartPage {
+    ChartPage {
+      schema_version: crate::graph::SCHEMA_VERSION,
+      title: title.to_string(),
+      updated_at: "2024-02-01 00:00:00".to_string(),
+      sections,
+    }
+  }
+
+  #[test]
+  fn test_render_site() {
+    let traffic = |title: &str, y: u64| {
+      page(
+        title,
+        vec![
+          ChartSection {
+            title: "All".to_string(),
+            datasets: vec![dataset("vpc", y)],
+          },
+          ChartSection {
+            title: "Networking".to_string(),
+            datasets: vec![dataset("vpc", y)],
+          },
+        ],
+      )
+    };
+    let pages = vec![
+      ("github-views.json".to_string(), traffic("Repository Page Views", 12)),
+      ("github-clones.json".to_string(), traffic("Repository Clones", 34)),
+      (
+        "registry-downloads.json".to_string(),
+        page(
+          "Terraform Registry Downloads",
+          vec![ChartSection {
+            title: "vpc".to_string(),
+            datasets: vec![dataset("v5.0", 100), dataset("v6.0", 20)],
+          }],
+        ),
+      ),
+    ];
+
+    let dir = std::env::temp_dir().join(format!("tmd-html-{}", std::process::id()));
+    render(&pages, &dir).unwrap();
+
+    let index = fs::read_to_string(dir.join("index.html")).unwrap();
+    assert!(index.contains(r#"<td><a href="modules/vpc.html">vpc</a></td><td>120</td><td>12</td><td>34</td>"#));
+
+    let module = fs::read_to_string(dir.join("modules").join("vpc.html")).unwrap();
+    assert!(module.contains("<h1>terraform-aws-vpc</h1>"));
+    assert!(module.contains("<svg"));
+
+    let networking = fs::read_to_string(dir.join("categories").join("networking.html")).unwrap();
+    assert!(networking.contains("<h2>Repository Page Views</h2>"));
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
@@ -0,0 +1,250 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Result;
+use chrono::{Days, NaiveDate};
+use serde::Serialize;
+
+use crate::registry::Summary;
+
+/// Comparison of a metric over equal windows before and after a release
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Change {
+  /// `views`, `clones`, `downloads` (all majors) or `downloads vN` (a single major)
+  pub metric: String,
+  pub before: u64,
+  pub after: u64,
+  /// Absolute change from the window before to the window after the release
+  pub change: i64,
+  /// Relative change as a percentage, when there was activity before the release
+  pub relative: Option<f64>,
+}
+
+impl Change {
+  fn new(metric: impl Into<String>, before: u64, after: u64) -> Self {
+    Self {
+      metric: metric.into(),
+      before,
+      after,
+      change: after as i64 - before as i64,
+      relative: (before > 0).then(|| (after as f64 - before as f64) / before as f64 * 100.0),
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReleaseImpact {
+  pub module: String,
+  pub version: String,
+  pub released_at: String,
+  pub window_days: u64,
+  /// Whether the collected data covers the full windows before and after the release
+  pub window_complete: bool,
+  pub changes: Vec<Change>,
+}
+
+/// Sum of the daily counts in `[start, end)`
+fn window_sum(daily: &BTreeMap<NaiveDate, u64>, start: NaiveDate, end: NaiveDate) -> u64 {
+  daily.range(start..end).map(|(_, v)| v).sum()
+}
+
+/// Cumulative downloads per major from the latest snapshot on or before a date
+///
+/// When the data starts after the date, such as for a new module, the earliest snapshot is the baseline instead, so
+/// the downloads collected before it aren't counted as gained in the window.
+fn downloads_at(snapshots: &BTreeMap<NaiveDate, Vec<Summary>>, date: NaiveDate) -> BTreeMap<String, u64> {
+  snapshots
+    .range(..=date)
+    .next_back()
+    .or_else(|| snapshots.first_key_value())
+    .map(|(_, summary)| summary.iter().map(|s| (s.major_version.clone(), s.downloads)).collect())
+    .unwrap_or_default()
+}
+
+/// First and last date of a series, if it has any entries
+fn span<V>(series: &BTreeMap<NaiveDate, V>) -> Option<(NaiveDate, NaiveDate)> {
+  series
+    .first_key_value()
+    .zip(series.last_key_value())
+    .map(|(f, l)| (*f.0, *l.0))
+}
+
+/// Whether every data source with data, given as its first and last date, has an entry on or before `start` and on
+/// or after `end`
+fn covers(sources: &[Option<(NaiveDate, NaiveDate)>], start: NaiveDate, end: NaiveDate) -> bool {
+  let spans: Vec<&(NaiveDate, NaiveDate)> = sources.iter().flatten().collect();
+  !spans.is_empty() && spans.iter().all(|(first, last)| *first <= start && *last >= end)
+}
+
+/// Compare views, clones and registry downloads (total and per major) in the windows before and after a release
+fn compare(
+  views: &BTreeMap<NaiveDate, u64>,
+  clones: &BTreeMap<NaiveDate, u64>,
+  snapshots: &BTreeMap<NaiveDate, Vec<Summary>>,
+  released: NaiveDate,
+  window: u64,
+) -> Vec<Change> {
+  let start = released - Days::new(window);
+  let end = released + Days::new(window);
+
+  let mut changes = vec![
+    Change::new(
+      "views",
+      window_sum(views, start, released),
+      window_sum(views, released, end),
+    ),
+    Change::new(
+      "clones",
+      window_sum(clones, start, released),
+      window_sum(clones, released, end),
+    ),
+  ];
+
+  // Snapshots are cumulative, so the downloads in a window are the difference between its boundaries
+  let (at_start, at_release, at_end) = (
+    downloads_at(snapshots, start),
+    downloads_at(snapshots, released),
+    downloads_at(snapshots, end),
+  );
+
+  let mut majors: Vec<&String> = at_end.keys().collect();
+  majors.sort_by_key(|m| m.parse::<u64>().unwrap_or(0));
+
+  let (mut total_before, mut total_after) = (0, 0);
+  let mut per_major = Vec::new();
+  for major in majors {
+    let get = |m: &BTreeMap<String, u64>| m.get(major).copied().unwrap_or(0);
+    let before = get(&at_release).saturating_sub(get(&at_start));
+    let after = get(&at_end).saturating_sub(get(&at_release));
+    total_before += before;
+    total_after += after;
+    per_major.push(Change::new(format!("downloads v{major}"), before, after));
+  }
+
+  changes.push(Change::new("downloads", total_before, total_after));
+  changes.extend(per_major);
+  changes
+}
+
+/// Measure the change in traffic and downloads around the release of a module version
+pub(crate) async fn analyze(
+  data_path: &Path,
+  module: &str,
+  version: &str,
+  window: u64,
+  versions: &crate::config::VersionConfig,
+) -> Result<ReleaseImpact> {
+  let released = crate::registry::published_at(module, version).await?;
+
+  let views = crate::github::daily_traffic(data_path, module, "views")?;
+  let clones = crate::github::daily_traffic(data_path, module, "clones")?;
+  let snapshots = crate::registry::load_snapshots(&data_path.join("registry").join(module), &versions.rules(module))?;
+
+  let start = released - Days::new(window);
+  let end = released + Days::new(window);
+  let window_complete = covers(&[span(&views), span(&clones), span(&snapshots)], start, end);
+
+  Ok(ReleaseImpact {
+    module: module.to_string(),
+    version: version.trim_start_matches('v').to_string(),
+    released_at: released.to_string(),
+    window_days: window,
+    window_complete,
+    changes: compare(&views, &clones, &snapshots, released, window),
+  })
+}
+
+/// Render the release impact as a plain text table
+pub(crate) fn table(impact: &ReleaseImpact) -> String {
+  let mut lines = vec![
+    format!(
+      "{} v{} released {} ({} day windows{})",
+      impact.module,
+      impact.version,
+      impact.released_at,
+      impact.window_days,
+      if impact.window_complete {
+        ""
+      } else {
+        ", windows are incomplete"
+      }
+    ),
+    format!(
+      "{:<16} {:>12} {:>12} {:>12} {:>9}",
+      "Metric", "Before", "After", "Change", "Relative"
+    ),
+  ];
+
+  for c in &impact.changes {
+    let relative = c
+      .relative
+      .map(|r| format!("{r:+.1}%"))
+      .unwrap_or_else(|| "-".to_string());
+    lines.push(format!(
+      "{:<16} {:>12} {:>12} {:>+12} {relative:>9}",
+      c.metric, c.before, c.after, c.change
+    ));
+  }
+
+  lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 3, day).unwrap()
+  }
+
+  fn summary(major: &str, downloads: u64) -> Summary {
+    Summary {
+      downloads,
+      major_version: major.to_string(),
+      created_at: String::new(),
+      prerelease_downloads: 0,
+      latest_release_at: String::new(),
+      releases: 0,
+    }
+  }
+
+  #[test]
+  fn test_compare_windows() {
+    let views: BTreeMap<NaiveDate, u64> = (1..=20).map(|d| (date(d), if d < 10 { 10 } else { 20 })).collect();
+    let clones = BTreeMap::from([(date(9), 5), (date(12), 15)]);
+    let snapshots = BTreeMap::from([
+      (date(5), vec![summary("1", 100)]),
+      (date(10), vec![summary("1", 150)]),
+      (date(15), vec![summary("1", 160), summary("2", 90)]),
+    ]);
+
+    let changes = compare(&views, &clones, &snapshots, date(10), 5);
+
+    assert_eq!(changes[0], Change::new("views", 50, 100));
+    assert_eq!(changes[0].relative, Some(100.0));
+    assert_eq!(changes[1], Change::new("clones", 5, 15));
+    assert_eq!(changes[2], Change::new("downloads", 50, 100));
+    assert_eq!(changes[3], Change::new("downloads v1", 50, 10));
+    assert_eq!(changes[4].metric, "downloads v2");
+    assert_eq!(changes[4].relative, None);
+    assert_eq!(changes[4].change, 90);
+
+    // Without a snapshot before the window, the first snapshot is the baseline rather than zero
+    let changes = compare(&views, &clones, &snapshots, date(8), 5);
+    assert_eq!(changes[2], Change::new("downloads", 0, 50));
+  }
+
+  #[test]
+  fn test_covers() {
+    let full = Some((date(5), date(20)));
+    assert!(covers(&[full, None], date(5), date(15)));
+    assert!(!covers(&[full, Some((date(8), date(20)))], date(5), date(15)));
+    assert!(!covers(&[full], date(5), date(25)));
+    assert!(!covers(&[None, None], date(5), date(15)));
+
+    // Clones that start inside the window make it incomplete even when the views cover it
+    let views: BTreeMap<NaiveDate, u64> = (5..=20).map(|d| (date(d), 1)).collect();
+    let clones: BTreeMap<NaiveDate, u64> = (10..=20).map(|d| (date(d), 1)).collect();
+    assert_eq!(span(&clones), Some((date(10), date(20))));
+    assert!(!covers(&[span(&views), span(&clones)], date(5), date(15)));
+  }
+}
@@ -0,0 +1,413 @@
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  fs, io,
+  path::Path,
+};
+
+use anyhow::{Context, Result};
+use chrono::{Months, NaiveDate};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{clock::Clock, config::VersionConfig, topics::Categories};
+
+/// Directory (under the data path) where rank history is recorded, one file per month
+const RANKINGS_DIR: &str = "rankings";
+
+/// Number of movers listed per category on the leaderboard page
+const MAX_MOVERS: usize = 5;
+
+type MonthlySeries = BTreeMap<String, BTreeMap<NaiveDate, u64>>;
+
+/// Position of a module within its category for each ranked metric (1 is the highest)
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, JsonSchema)]
+pub struct Ranks {
+  pub downloads: usize,
+  pub views: usize,
+  pub clones: usize,
+  /// Not ranked when there is no previous month of downloads to compare against
+  pub growth: Option<usize>,
+}
+
+/// Metrics and ranks for a single module in a single month
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct Standing {
+  pub module: String,
+  pub downloads: u64,
+  pub views: u64,
+  pub clones: u64,
+  /// Month over month change in registry downloads, as a percentage
+  pub growth: Option<f64>,
+  pub ranks: Ranks,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CategoryStandings {
+  pub title: String,
+  pub standings: Vec<Standing>,
+}
+
+/// Rankings for every category in a single month, recorded as rank history
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Ranking {
+  pub month: String,
+  pub categories: Vec<CategoryStandings>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct LeaderboardEntry {
+  #[serde(flatten)]
+  pub standing: Standing,
+  /// Places moved in the downloads ranking since the previous month (positive is up)
+  pub movement: Option<i64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct LeaderboardSection {
+  pub title: String,
+  pub entries: Vec<LeaderboardEntry>,
+  pub movers: Vec<String>,
+}
+
+/// Leaderboard page written to `site/public/data/leaderboard.json`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Leaderboard {
+  pub schema_version: u32,
+  pub title: String,
+  pub updated_at: String,
+  pub month: String,
+  pub previous_month: Option<String>,
+  pub sections: Vec<LeaderboardSection>,
+}
+
+/// Monthly metrics for every tracked module
+struct Inputs {
+  downloads: MonthlySeries,
+  views: MonthlySeries,
+  clones: MonthlySeries,
+}
+
+impl Inputs {
+  fn load(data_path: &Path, versions: &VersionConfig, clock: &Clock) -> Result<Self> {
+    Ok(Self {
+      downloads: crate::registry::monthly_downloads(data_path, versions, clock)?,
+      views: crate::github::monthly_traffic(data_path, "views", clock)?,
+      clones: crate::github::monthly_traffic(data_path, "clones", clock)?,
+    })
+  }
+
+  fn modules(&self) -> BTreeSet<&str> {
+    self
+      .downloads
+      .keys()
+      .chain(self.views.keys())
+      .chain(self.clones.keys())
+      .map(String::as_str)
+      .collect()
+  }
+
+  /// Most recent complete month with registry download data
+  fn latest_month(&self) -> Option<NaiveDate> {
+    self
+      .downloads
+      .values()
+      .filter_map(|m| m.keys().next_back())
+      .max()
+      .copied()
+  }
+
+  fn value(series: &MonthlySeries, module: &str, month: NaiveDate) -> Option<u64> {
+    series.get(module).and_then(|m| m.get(&month)).copied()
+  }
+
+  fn rank(&self, month: NaiveDate, categories: &Categories) -> Result<Ranking> {
+    let previous = month - Months::new(1);
+
+    let mut sections = Vec::new();
+    for (title, category) in crate::CATEGORY_SECTIONS {
+      let mut standings = Vec::new();
+      for module in self.modules() {
+        if !categories.contains(category, module)? {
+          continue;
+        }
+
+        let downloads = Self::value(&self.downloads, module, month).unwrap_or(0);
+        let growth = Self::value(&self.downloads, module, previous)
+          .filter(|p| *p > 0)
+          .map(|p| (downloads as f64 - p as f64) / p as f64 * 100.0);
+
+        standings.push(Standing {
+          module: module.to_string(),
+          downloads,
+          views: Self::value(&self.views, module, month).unwrap_or(0),
+          clones: Self::value(&self.clones, module, month).unwrap_or(0),
+          growth,
+          ranks: Ranks::default(),
+        });
+      }
+
+      assign_ranks(&mut standings);
+      sections.push(CategoryStandings {
+        title: title.to_string(),
+        standings,
+      });
+    }
+
+    Ok(Ranking {
+      month: month.format("%Y-%m").to_string(),
+      categories: sections,
+    })
+  }
+}
+
+/// Standard competition ranking ("1224"), highest value first
+fn competition_ranks<T: PartialOrd>(values: &[Option<T>]) -> Vec<Option<usize>> {
+  values
+    .iter()
+    .map(|v| {
+      v.as_ref()
+        .map(|v| 1 + values.iter().flatten().filter(|other| *other > v).count())
+    })
+    .collect()
+}
+
+fn assign_ranks(standings: &mut [Standing]) {
+  let downloads = competition_ranks(&standings.iter().map(|s| Some(s.downloads)).collect::<Vec<_>>());
+  let views = competition_ranks(&standings.iter().map(|s| Some(s.views)).collect::<Vec<_>>());
+  let clones = competition_ranks(&standings.iter().map(|s| Some(s.clones)).collect::<Vec<_>>());
+  let growth = competition_ranks(&standings.iter().map(|s| s.growth).collect::<Vec<_>>());
+
+  for (i, standing) in standings.iter_mut().enumerate() {
+    standing.ranks = Ranks {
+      downloads: downloads[i].unwrap_or_default(),
+      views: views[i].unwrap_or_default(),
+      clones: clones[i].unwrap_or_default(),
+      growth: growth[i],
+    };
+  }
+
+  standings.sort_by(|a, b| a.ranks.downloads.cmp(&b.ranks.downloads).then(a.module.cmp(&b.module)));
+}
+
+fn parse_month(month: &str) -> Result<NaiveDate> {
+  NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d").with_context(|| format!("Invalid month: {month}"))
+}
+
+fn history_path(data_path: &Path, month: &str) -> std::path::PathBuf {
+  data_path.join(RANKINGS_DIR).join(format!("{month}.json"))
+}
+
+/// Load a recorded ranking for a month, if one exists
+fn load_history(data_path: &Path, month: &str) -> Result<Option<Ranking>> {
+  let path = history_path(data_path, month);
+  match fs::read_to_string(&path) {
+    Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+    Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+  }
+}
+
+/// Record a ranking in the rank history, replacing any existing entry for the same month
+fn write_history(data_path: &Path, ranking: &Ranking) -> Result<()> {
+  fs::create_dir_all(data_path.join(RANKINGS_DIR))?;
+  let json = serde_json::to_string_pretty(ranking)?;
+  fs::write(history_path(data_path, &ranking.month), json)?;
+  Ok(())
+}
+
+/// Compare the current ranking against the previous month, producing the leaderboard sections
+fn leaderboard_sections(current: Ranking, previous: Option<&Ranking>) -> Vec<LeaderboardSection> {
+  current
+    .categories
+    .into_iter()
+    .map(|category| {
+      let previous_ranks: BTreeMap<&str, usize> = previous
+        .and_then(|p| p.categories.iter().find(|c| c.title == category.title))
+        .map(|c| {
+          c.standings
+            .iter()
+            .map(|s| (s.module.as_str(), s.ranks.downloads))
+            .collect()
+        })
+        .unwrap_or_default();
+
+      let entries: Vec<LeaderboardEntry> = category
+        .standings
+        .into_iter()
+        .map(|standing| {
+          let movement = previous_ranks
+            .get(standing.module.as_str())
+            .map(|p| *p as i64 - standing.ranks.downloads as i64);
+          LeaderboardEntry { standing, movement }
+        })
+        .collect();
+
+      let mut moved: Vec<(&str, i64)> = entries
+        .iter()
+        .filter_map(|e| e.movement.filter(|m| *m != 0).map(|m| (e.standing.module.as_str(), m)))
+        .collect();
+      moved.sort_by(|a, b| b.1.abs().cmp(&a.1.abs()).then(a.0.cmp(b.0)));
+
+      let movers = moved
+        .into_iter()
+        .take(MAX_MOVERS)
+        .map(|(module, m)| {
+          let direction = if m > 0 { "up" } else { "down" };
+          let places = if m.abs() == 1 { "place" } else { "places" };
+          format!("{module} {direction} {} {places} this month", m.abs())
+        })
+        .collect();
+
+      LeaderboardSection {
+        title: category.title,
+        entries,
+        movers,
+      }
+    })
+    .collect()
+}
+
+/// Rank the modules for a month (the latest complete month by default) and record it in the rank history
+pub(crate) fn rank(
+  data_path: &Path,
+  month: Option<&str>,
+  categories: &Categories,
+  versions: &VersionConfig,
+  clock: &Clock,
+) -> Result<Ranking> {
+  let inputs = Inputs::load(data_path, versions, clock)?;
+  let month = match month {
+    Some(m) => parse_month(m)?,
+    None => inputs
+      .latest_month()
+      .ok_or_else(|| anyhow::anyhow!("No complete month of registry data to rank"))?,
+  };
+
+  let ranking = inputs.rank(month, categories)?;
+  info!("Recording rankings for {}", ranking.month);
+  write_history(data_path, &ranking)?;
+
+  Ok(ranking)
+}
+
+/// Output the leaderboard JSON for the Astro site
+pub(crate) fn graph(
+  data_path: &Path,
+  output_path: &Path,
+  categories: &Categories,
+  versions: &VersionConfig,
+  clock: &Clock,
+) -> Result<()> {
+  let timestamp = clock.timestamp();
+
+  let inputs = Inputs::load(data_path, versions, clock)?;
+  let Some(month) = inputs.latest_month() else {
+    info!("No complete month of registry data, skipping leaderboard.json");
+    return Ok(());
+  };
+
+  let current = inputs.rank(month, categories)?;
+  let previous_month = (month - Months::new(1)).format("%Y-%m").to_string();
+  let previous = match load_history(data_path, &previous_month)? {
+    Some(p) => Some(p),
+    None
+      if inputs
+        .downloads
+        .values()
+        .any(|m| m.contains_key(&(month - Months::new(1)))) =>
+    {
+      Some(inputs.rank(month - Months::new(1), categories)?)
+    }
+    None => None,
+  };
+
+  let page = Leaderboard {
+    schema_version: crate::graph::SCHEMA_VERSION,
+    title: "Module Leaderboard".to_string(),
+    updated_at: timestamp,
+    month: current.month.clone(),
+    previous_month: previous.as_ref().map(|p| p.month.clone()),
+    sections: leaderboard_sections(current, previous.as_ref()),
+  };
+
+  info!("Writing leaderboard.json");
+  let json = serde_json::to_string_pretty(&page)?;
+  fs::write(output_path.join("leaderboard.json"), json)?;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn standing(module: &str, downloads: u64, growth: Option<f64>) -> Standing {
+    Standing {
+      module: module.to_string(),
+      downloads,
+      views: 0,
+      clones: 0,
+      growth,
+      ranks: Ranks::default(),
+    }
+  }
+
+  #[test]
+  fn test_competition_ranks_ties() {
+    let ranks = competition_ranks(&[Some(10), Some(30), Some(10), None, Some(5)]);
+    assert_eq!(ranks, vec![Some(2), Some(1), Some(2), None, Some(4)]);
+  }
+
+  #[test]
+  fn test_assign_ranks_sorts_by_downloads() {
+    let mut standings = vec![
+      standing("vpc", 10, None),
+      standing("eks", 30, Some(5.0)),
+      standing("rds", 20, Some(10.0)),
+    ];
+    assign_ranks(&mut standings);
+
+    let modules: Vec<&str> = standings.iter().map(|s| s.module.as_str()).collect();
+    assert_eq!(modules, vec!["eks", "rds", "vpc"]);
+    assert_eq!(standings[1].ranks.growth, Some(1));
+    assert_eq!(standings[2].ranks.growth, None);
+  }
+
+  #[test]
+  fn test_load_history_errors() {
+    let dir = std::env::temp_dir().join(format!("tmd-rank-history-{}", std::process::id()));
+    assert!(load_history(&dir, "2024-01").unwrap().is_none());
+
+    // A history entry that can't be read is an error rather than a missing month
+    fs::create_dir_all(history_path(&dir, "2024-01")).unwrap();
+    assert!(load_history(&dir, "2024-01").is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn test_leaderboard_movers() {
+    let rank_of = |month: &str, modules: &[(&str, u64)]| {
+      let mut standings: Vec<Standing> = modules.iter().map(|(m, d)| standing(m, *d, None)).collect();
+      assign_ranks(&mut standings);
+      Ranking {
+        month: month.to_string(),
+        categories: vec![CategoryStandings {
+          title: "All".to_string(),
+          standings,
+        }],
+      }
+    };
+
+    let previous = rank_of("2024-01", &[("eks", 40), ("vpc", 30), ("rds", 20), ("rds-aurora", 10)]);
+    let current = rank_of("2024-02", &[("eks", 40), ("vpc", 5), ("rds", 20), ("rds-aurora", 30)]);
+
+    let sections = leaderboard_sections(current, Some(&previous));
+    assert_eq!(
+      sections[0].movers,
+      vec!["rds-aurora up 2 places this month", "vpc down 2 places this month"]
+    );
+    assert_eq!(sections[0].entries[0].movement, Some(0));
+  }
+}
@@ -1,7 +1,26 @@
+pub(crate) mod adoption;
+pub(crate) mod alerts;
+pub(crate) mod badges;
 pub mod cli;
+pub mod clock;
+pub mod config;
+pub(crate) mod detail;
+pub(crate) mod discover;
+pub mod error;
+pub(crate) mod export;
+pub(crate) mod forecast;
 pub(crate) mod github;
 pub mod graph;
+pub(crate) mod html;
+pub(crate) mod impact;
+pub(crate) mod leaderboard;
+pub(crate) mod metrics;
 pub(crate) mod registry;
+pub(crate) mod server;
+pub mod store;
+pub(crate) mod svg;
+pub mod topics;
+pub(crate) mod totals;
 
 use std::{
   collections::{HashMap, HashSet},
@@ -14,6 +33,24 @@ const SERVERLESS: &str = "serverless";
 const NETWORKING: &str = "networking";
 const OTHER: &str = "other";
 
+/// Section titles and the category each one is filtered to (`None` means all modules)
+pub(crate) const CATEGORY_SECTIONS: [(&str, Option<&str>); 6] = [
+  ("All", None),
+  ("Compute", Some(COMPUTE)),
+  ("Serverless", Some(SERVERLESS)),
+  ("Data", Some(DATA)),
+  ("Networking", Some(NETWORKING)),
+  ("Other", Some(OTHER)),
+];
+
+/// Primary category of the modules in several built-in categories; their other categories are secondary tags
+const PRIMARY: [(&str, &str); 4] = [
+  ("app-runner", COMPUTE),
+  ("lambda", SERVERLESS),
+  ("memory-db", DATA),
+  ("rds-proxy", NETWORKING),
+];
+
 static CATEGORIES: LazyLock<HashMap<&str, HashSet<&str>>> = LazyLock::new(|| {
   HashMap::from([
     (
@@ -104,6 +141,37 @@ static CATEGORIES: LazyLock<HashMap<&str, HashSet<&str>>> = LazyLock::new(|| {
   ])
 });
 
+/// File name of a data directory entry without its extension, which is a module name or snapshot date
+pub(crate) fn file_stem(path: &std::path::Path) -> error::Result<&str> {
+  path
+    .file_stem()
+    .and_then(|s| s.to_str())
+    .ok_or_else(|| error::Error::Storage {
+      path: path.to_path_buf(),
+      source: std::io::Error::new(std::io::ErrorKind::InvalidData, "missing or non-UTF8 file name"),
+    })
+}
+
+/// Primary built-in category: the only category of a module, or its entry in `PRIMARY`
+pub(crate) fn builtin_primary(module: &str) -> Option<&'static str> {
+  PRIMARY
+    .iter()
+    .find(|(m, _)| *m == module)
+    .map(|(_, c)| *c)
+    .or_else(|| builtin_categories(module).first().copied())
+}
+
+/// Categories of the built-in `CATEGORIES` sets a module belongs to, sorted by name
+pub(crate) fn builtin_categories(module: &str) -> Vec<&'static str> {
+  let mut categories: Vec<&str> = CATEGORIES
+    .iter()
+    .filter(|(_, modules)| modules.contains(module))
+    .map(|(c, _)| *c)
+    .collect();
+  categories.sort();
+  categories
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -124,6 +192,33 @@ mod tests {
     }
   }
 
+  #[test]
+  fn test_category_sections_are_known() {
+    for (_, category) in CATEGORY_SECTIONS {
+      if let Some(c) = category {
+        assert!(CATEGORIES.contains_key(c), "Section category '{c}' should exist");
+      }
+    }
+  }
+
+  #[test]
+  fn test_primary_category() {
+    for module in CATEGORIES.values().flatten() {
+      let categories = builtin_categories(module);
+      let primary = builtin_primary(module).unwrap();
+      assert!(
+        categories.contains(&primary),
+        "{module}: {primary} is not one of {categories:?}"
+      );
+      if categories.len() > 1 {
+        assert!(
+          PRIMARY.iter().any(|(m, _)| m == module),
+          "{module} needs a primary category"
+        );
+      }
+    }
+  }
+
   #[test]
   fn test_s3_object_not_in_categories() {
     for (_, modules) in CATEGORIES.iter() {
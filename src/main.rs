@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use anyhow::Result;
 use clap::Parser;
-use terraform_module_data::{cli as tmd, graph};
+use terraform_module_data::{cli as tmd, config::Config, graph};
 use tracing_log::AsTrace;
 use tracing_subscriber::FmtSubscriber;
 
@@ -18,9 +18,21 @@ async fn main() -> Result<()> {
 
   // Root directory where data is stored
   let data_path = PathBuf::from("data");
+  let config = Config::load(&cli.config)?;
 
   match &cli.command {
-    tmd::Commands::CollectData(download) => download.collect(data_path).await,
-    tmd::Commands::Graph => graph::graph(&data_path),
+    tmd::Commands::CollectData(download) => download.collect(data_path, &config).await,
+    tmd::Commands::Graph(args) => graph::graph(&data_path, args.format, &args.options(), &args.clock(), &config),
+    tmd::Commands::Rank(rank) => rank.rank(data_path, &config),
+    tmd::Commands::Adoption(adoption) => adoption.report(data_path, &config),
+    tmd::Commands::Forecast(forecast) => forecast.backtest(data_path, &config),
+    tmd::Commands::Alerts(alerts) => alerts.run(data_path, &config).await,
+    tmd::Commands::ReleaseImpact(release) => release.analyze(data_path, &config).await,
+    tmd::Commands::Render(render) => render.render(data_path, &config),
+    tmd::Commands::Serve(serve) => serve.serve(data_path, &config).await,
+    tmd::Commands::Metrics(metrics) => metrics.export(data_path, &config),
+    tmd::Commands::Export(export) => export.export(data_path, &config).await,
+    tmd::Commands::Discover(discover) => discover.discover(&cli.config, &config).await,
+    tmd::Commands::Categories(categories) => categories.report(data_path, &config),
   }
 }
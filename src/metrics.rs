@@ -0,0 +1,171 @@
+use std::{fmt::Write as _, path::Path};
+
+use anyhow::Result;
+
+use crate::{config::VersionConfig, github::TrafficEntry, topics::Categories};
+
+/// Content type of the OpenMetrics text exposition format
+pub(crate) const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// A gauge metric family and its samples, each sample being a set of labels and a value
+struct Gauge {
+  name: &'static str,
+  help: &'static str,
+  samples: Vec<(Vec<(&'static str, String)>, u64)>,
+}
+
+impl Gauge {
+  fn new(name: &'static str, help: &'static str) -> Self {
+    Self {
+      name,
+      help,
+      samples: Vec::new(),
+    }
+  }
+
+  fn write(&self, out: &mut String) {
+    let _ = writeln!(out, "# TYPE {} gauge\n# HELP {} {}", self.name, self.name, self.help);
+    for (labels, value) in &self.samples {
+      let labels: Vec<String> = labels.iter().map(|(k, v)| format!("{k}=\"{}\"", escape(v))).collect();
+      let _ = writeln!(out, "{}{{{}}} {value}", self.name, labels.join(","));
+    }
+  }
+}
+
+/// Value of a traffic entry exported by a gauge
+type Field = fn(&TrafficEntry) -> u64;
+
+/// Escape a label value for the text exposition format
+fn escape(value: &str) -> String {
+  value.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n")
+}
+
+/// Module and primary category labels; modules without a category have no category label
+fn module_labels(module: &str, categories: &Categories) -> Vec<(&'static str, String)> {
+  let mut labels = vec![("module", module.to_string())];
+  if let Some(category) = categories.primary(module) {
+    labels.push(("category", category.to_string()));
+  }
+  labels
+}
+
+/// Latest registry downloads and GitHub traffic of every module in the OpenMetrics text format
+pub(crate) fn render(data_path: &Path, categories: &Categories, versions: &VersionConfig) -> Result<String> {
+  let mut downloads = Gauge::new(
+    "tmd_registry_downloads",
+    "Cumulative Terraform registry downloads of a major version in the latest snapshot",
+  );
+  for module in crate::registry::modules(data_path)? {
+    let snapshots =
+      crate::registry::load_snapshots(&data_path.join("registry").join(&module), &versions.rules(&module))?;
+    for summary in snapshots.into_values().next_back().unwrap_or_default() {
+      let mut labels = module_labels(&module, categories);
+      labels.push(("major", summary.major_version));
+      downloads.samples.push((labels, summary.downloads));
+    }
+  }
+
+  let count = |e: &TrafficEntry| e.count;
+  let uniques = |e: &TrafficEntry| e.uniques;
+  let mut traffic: [(&str, Field, Gauge); 4] = [
+    (
+      "views",
+      count,
+      Gauge::new(
+        "tmd_github_views",
+        "GitHub repository page views on the latest day collected",
+      ),
+    ),
+    (
+      "views",
+      uniques,
+      Gauge::new(
+        "tmd_github_views_uniques",
+        "Unique GitHub repository visitors on the latest day collected",
+      ),
+    ),
+    (
+      "clones",
+      count,
+      Gauge::new(
+        "tmd_github_clones",
+        "GitHub repository clones on the latest day collected",
+      ),
+    ),
+    (
+      "clones",
+      uniques,
+      Gauge::new(
+        "tmd_github_clones_uniques",
+        "Unique GitHub repository cloners on the latest day collected",
+      ),
+    ),
+  ];
+  for module in crate::github::modules(data_path)? {
+    for (data_type, value, gauge) in &mut traffic {
+      let summary = crate::github::load_traffic(data_path, &module, data_type)?;
+      if let Some(entry) = summary.values().next_back() {
+        gauge.samples.push((module_labels(&module, categories), value(entry)));
+      }
+    }
+  }
+
+  let mut out = String::new();
+  downloads.write(&mut out);
+  for (_, _, gauge) in &traffic {
+    gauge.write(&mut out);
+  }
+  out.push_str("# EOF\n");
+
+  Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use super::*;
+
+  #[test]
+  fn test_render() {
+    let data_path = std::env::temp_dir().join(format!("tmd-metrics-{}", std::process::id()));
+    let registry = data_path.join("registry").join("lambda");
+    fs::create_dir_all(&registry).unwrap();
+    fs::write(
+      registry.join("2024-01-01.json"),
+      r#"[{"downloads": 1, "major_version": "7", "created_at": "2024-01-01"}]"#,
+    )
+    .unwrap();
+    fs::write(
+      registry.join("2024-01-02.json"),
+      r#"[{"downloads": 10, "major_version": "6", "created_at": "2023-01-01"},
+          {"downloads": 20, "major_version": "7", "created_at": "2024-01-01"}]"#,
+    )
+    .unwrap();
+
+    let github = data_path.join("github").join("lambda");
+    fs::create_dir_all(&github).unwrap();
+    for (file, count) in [("views.json", 30), ("clones.json", 4)] {
+      let traffic = format!(
+        r#"{{"2024-01-01": {{"count": 1, "timestamp": "2024-01-01T00:00:00Z", "uniques": 1}},
+            "2024-01-02": {{"count": {count}, "timestamp": "2024-01-02T00:00:00Z", "uniques": 3}}}}"#
+      );
+      fs::write(github.join(file), traffic).unwrap();
+    }
+
+    let metrics = render(&data_path, &Categories::builtin(), &VersionConfig::default()).unwrap();
+    fs::remove_dir_all(data_path).unwrap();
+
+    assert!(metrics.starts_with("# TYPE tmd_registry_downloads gauge\n"));
+    assert!(metrics.contains("tmd_registry_downloads{module=\"lambda\",category=\"serverless\",major=\"6\"} 10\n"));
+    assert!(metrics.contains("tmd_registry_downloads{module=\"lambda\",category=\"serverless\",major=\"7\"} 20\n"));
+    assert!(metrics.contains("tmd_github_views{module=\"lambda\",category=\"serverless\"} 30\n"));
+    assert!(metrics.contains("tmd_github_clones_uniques{module=\"lambda\",category=\"serverless\"} 3\n"));
+    assert!(metrics.ends_with("# EOF\n"));
+  }
+
+  #[test]
+  fn test_escape() {
+    assert_eq!(escape(r#"a"b\c"#), r#"a\"b\\c"#);
+  }
+}
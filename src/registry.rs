@@ -1,16 +1,21 @@
 use std::{
-  collections::BTreeMap,
+  collections::{BTreeMap, BTreeSet},
   fs,
   path::{Path, PathBuf},
 };
 
-use anyhow::{Result, bail};
 use chrono::prelude::*;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use tracing::info;
 use url::Url;
 
+use crate::{
+  clock::Clock,
+  config::{VersionConfig, Versions},
+  error::{Error, Result},
+  graph::ChartOptions,
+};
+
 #[derive(Debug, Deserialize, Serialize)]
 struct Response {
   data: Data,
@@ -65,54 +70,118 @@ struct IncludedAttributes {
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-struct Summary {
-  downloads: u64,
-  major_version: String,
-  created_at: String,
+pub(crate) struct Summary {
+  /// Downloads of every version of the major version, prereleases included
+  pub(crate) downloads: u64,
+  pub(crate) major_version: String,
+  /// Date of the first release of the major version, prereleases included
+  pub(crate) created_at: String,
+  /// Part of `downloads` from prereleases such as `2.0.0-beta.1`, absent in snapshots written before they were tracked
+  #[serde(default)]
+  pub(crate) prerelease_downloads: u64,
+  /// Date of the latest release of the major version, empty in snapshots written before it was tracked
+  #[serde(default)]
+  pub(crate) latest_release_at: String,
+  /// Number of releases of the major version, prereleases included
+  #[serde(default)]
+  pub(crate) releases: u64,
+}
+
+/// Parse a registry version, allowing a `v` prefix
+fn parse_version(version: &str) -> std::result::Result<semver::Version, semver::Error> {
+  semver::Version::parse(version.strip_prefix('v').unwrap_or(version))
+}
+
+impl IncludedAttributes {
+  /// Release date of the version: the earlier of its creation and publish dates
+  fn released_at(&self) -> Result<NaiveDate> {
+    let date = |timestamp: &str| chrono::DateTime::parse_from_rfc3339(timestamp).map(|t| t.date_naive());
+    let created_at =
+      date(&self.created_at).map_err(|e| Error::parse(format!("creation date of version {}", self.version), e))?;
+    Ok(date(&self.published_at).map_or(created_at, |published_at| published_at.min(created_at)))
+  }
 }
 
 impl Response {
-  fn summarize(&self) -> Result<BTreeMap<String, Summary>> {
+  /// Downloads of each major version, leaving out the 0.x releases and prereleases the version rules exclude
+  ///
+  /// Versions that aren't valid semver or have an invalid creation date are skipped with a warning rather than failing
+  /// the whole module.
+  fn summarize(&self, rules: &Versions) -> BTreeMap<String, Summary> {
+    let module = &self.data.attributes.name;
     let mut summary: BTreeMap<String, Summary> = BTreeMap::new();
+    let mut released: BTreeMap<String, (NaiveDate, NaiveDate)> = BTreeMap::new();
     for i in self.included.iter() {
-      let mut ver = i.attributes.version.split('.');
-      let major_version = ver
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("Invalid version format"))?
-        .to_string();
-      let minor_version = ver.next().ok_or_else(|| anyhow::anyhow!("Invalid version format"))?;
-      let patch_version = ver.next().ok_or_else(|| anyhow::anyhow!("Invalid version format"))?;
-      let key = format!("{:02}", major_version.parse::<u64>().unwrap_or(0));
-
-      if major_version == "0" {
+      let version = match parse_version(&i.attributes.version) {
+        Ok(version) => version,
+        Err(e) => {
+          tracing::warn!("Skipping {module} version {:?}: {e}", i.attributes.version);
+          continue;
+        }
+      };
+      let prerelease = !version.pre.is_empty();
+
+      if !rules.collects(version.major) {
+        continue;
+      }
+      if prerelease && !rules.include_prereleases {
         continue;
       }
 
+      let date = match i.attributes.released_at() {
+        Ok(date) => date,
+        Err(e) => {
+          tracing::warn!("Skipping {module} version {:?}: {e}", i.attributes.version);
+          continue;
+        }
+      };
+      let key = format!("{:02}", version.major);
+      let (first, latest) = released.entry(key.clone()).or_insert((date, date));
+      *first = date.min(*first);
+      *latest = date.max(*latest);
+
       let record = summary.entry(key).or_insert(Summary {
         downloads: 0,
-        major_version,
+        major_version: version.major.to_string(),
         created_at: "".to_string(),
+        prerelease_downloads: 0,
+        latest_release_at: "".to_string(),
+        releases: 0,
       });
 
+      record.releases += 1;
       record.downloads += i.attributes.downloads;
+      if prerelease {
+        record.prerelease_downloads += i.attributes.downloads;
+      }
+    }
 
-      if minor_version == "0" && patch_version == "0" {
-        let timestamp = chrono::DateTime::parse_from_rfc3339(&i.attributes.created_at)?;
-        record.created_at = timestamp.date_naive().to_string();
+    for (key, (first, latest)) in released {
+      if let Some(record) = summary.get_mut(&key) {
+        record.created_at = first.to_string();
+        record.latest_release_at = latest.to_string();
       }
     }
 
-    Ok(summary)
+    summary
+  }
+
+  /// The release of a version, matched as semver so `v1.2.3` and `1.2.3` are the same version
+  fn release(&self, version: &semver::Version) -> Option<&Included> {
+    self
+      .included
+      .iter()
+      .find(|i| parse_version(&i.attributes.version).is_ok_and(|v| v.cmp_precedence(version).is_eq()))
   }
 
   fn write(&self, path: PathBuf, data: BTreeMap<String, Summary>) -> Result<()> {
-    std::fs::create_dir_all(&path)?;
+    std::fs::create_dir_all(&path).map_err(|e| Error::storage(&path, e))?;
 
     let data = data.into_values().collect::<Vec<Summary>>();
     let utc: DateTime<Utc> = Utc::now();
     let file = path.join(format!("{}.json", utc.format("%Y-%m-%d")));
-    let json = serde_json::to_string_pretty(&data)?;
-    std::fs::write(file, json)?;
+    let json = serde_json::to_string_pretty(&data).map_err(|e| Error::storage(&file, e.into()))?;
+    std::fs::write(&file, json).map_err(|e| Error::storage(&file, e))?;
 
     Ok(())
   }
@@ -122,31 +191,47 @@ async fn get(module: &str) -> Result<Response> {
   let url = Url::parse_with_params(
     format!("https://registry.terraform.io/v2/modules/terraform-aws-modules/{module}/aws").as_str(),
     &[("include", "module-versions")],
-  )?;
+  )
+  .map_err(|e| Error::parse(format!("registry URL of {module}"), e))?;
 
-  let resp = Client::builder()
+  let client = Client::builder()
     .user_agent("Module Download Data")
-    .build()?
-    .get(url)
+    .build()
+    .map_err(|e| Error::network(&url, e))?;
+  let resp = client
+    .get(url.clone())
     .send()
-    .await?;
+    .await
+    .map_err(|e| Error::network(&url, e))?;
 
-  if !resp.status().is_success() {
-    bail!("Registry API returned {}", resp.status());
-  }
-  let response: Response = resp.json().await?;
-  Ok(response)
+  let resp = crate::error::check(&url, &format!("Registry module {module}"), resp)?;
+  let body = resp.text().await.map_err(|e| Error::network(&url, e))?;
+  serde_json::from_str(&body).map_err(|e| Error::parse(&url, e))
 }
 
-pub async fn collect(path: &Path, module: &str) -> Result<()> {
+pub async fn collect(path: &Path, module: &str, versions: &VersionConfig) -> Result<()> {
   // Terraform registry data
   let registry = get(module).await?;
   let registry_path = path.join("registry").join(module.to_lowercase());
-  registry.write(registry_path, registry.summarize()?)?;
+  registry.write(registry_path, registry.summarize(&versions.rules(module)))?;
 
   Ok(())
 }
 
+/// Date a specific version of a module was published to the registry
+pub(crate) async fn published_at(module: &str, version: &str) -> Result<NaiveDate> {
+  let parsed = parse_version(version).map_err(|e| Error::parse(format!("version {version}"), e))?;
+  let version = version.trim_start_matches('v');
+  let registry = get(module).await?;
+  let release = registry.release(&parsed).ok_or_else(|| Error::NotFound {
+    what: format!("Version {version} of {module} in the registry"),
+  })?;
+
+  let timestamp = chrono::DateTime::parse_from_rfc3339(&release.attributes.published_at)
+    .map_err(|e| Error::parse(format!("publish date of {module} {version}"), e))?;
+  Ok(timestamp.date_naive())
+}
+
 type Module = String;
 type ModuleData = BTreeMap<Module, Vec<VersionTrace>>;
 
@@ -157,42 +242,140 @@ struct VersionTrace {
   values: Vec<u64>,
 }
 
-fn collect_trace_data(data_path: &Path) -> Result<ModuleData> {
+fn collect_trace_data(
+  data_path: &Path,
+  options: &ChartOptions,
+  versions: &VersionConfig,
+  clock: &Clock,
+) -> Result<ModuleData> {
   let mut data = ModuleData::new();
 
-  for entry in fs::read_dir(data_path.join("registry"))? {
-    let mod_path = entry?.path();
-    let module = mod_path
-      .file_stem()
-      .ok_or_else(|| anyhow::anyhow!("Missing file stem for path: {:?}", mod_path))?
-      .to_str()
-      .ok_or_else(|| anyhow::anyhow!("Non-UTF8 file stem for path: {:?}", mod_path))?
-      .to_owned();
-
-    let traces = get_module_data_traces(&mod_path)?;
+  for module in modules(data_path)? {
+    let rules = versions.rules(&module);
+    let traces = get_module_data_traces(&data_path.join("registry").join(&module), options, &rules, clock)?;
     data.insert(module, traces);
   }
 
   Ok(data)
 }
 
-fn get_module_data_traces(mod_path: &Path) -> Result<Vec<VersionTrace>> {
-  let module_name = mod_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+/// Module names that have registry data collected, sorted by name
+pub(crate) fn modules(data_path: &Path) -> Result<Vec<String>> {
+  let mut modules = Vec::new();
+  let path = data_path.join("registry");
+  for entry in fs::read_dir(&path).map_err(|e| Error::storage(&path, e))? {
+    let entry = entry.map_err(|e| Error::storage(&path, e))?;
+    modules.push(crate::file_stem(&entry.path())?.to_owned());
+  }
+  modules.sort();
+
+  Ok(modules)
+}
+
+/// Load every daily snapshot saved for a module, keyed by snapshot date, without the major versions the rules hide
+pub(crate) fn load_snapshots(mod_path: &Path, rules: &Versions) -> Result<BTreeMap<NaiveDate, Vec<Summary>>> {
+  let mut snapshots = BTreeMap::new();
 
-  let mut daily: BTreeMap<String, BTreeMap<NaiveDate, u64>> = BTreeMap::new();
+  for fentry in fs::read_dir(mod_path).map_err(|e| Error::storage(mod_path, e))? {
+    let file_path = fentry.map_err(|e| Error::storage(mod_path, e))?.path();
+    let file_name = crate::file_stem(&file_path)?;
+    let file_data = fs::read_to_string(&file_path).map_err(|e| Error::storage(&file_path, e))?;
+    let summary = serde_json::from_str::<Vec<Summary>>(&file_data).map_err(|e| Error::parse(file_path.display(), e))?;
+
+    let timestamp =
+      NaiveDate::parse_from_str(file_name, "%Y-%m-%d").map_err(|e| Error::parse(file_path.display(), e))?;
+    snapshots.insert(timestamp, summary);
+  }
+
+  hide_versions(&mut snapshots, rules);
+  Ok(snapshots)
+}
+
+/// Drop hidden major versions from every snapshot
+///
+/// The download threshold is judged on the latest downloads of a major, so a shown major keeps its whole history.
+fn hide_versions(snapshots: &mut BTreeMap<NaiveDate, Vec<Summary>>, rules: &Versions) {
+  let mut latest: BTreeMap<&str, u64> = BTreeMap::new();
+  for s in snapshots.values().flatten() {
+    latest.insert(&s.major_version, s.downloads);
+  }
+  let hidden: BTreeSet<String> = latest
+    .into_iter()
+    .filter(|(major, downloads)| !rules.shows(major.parse().unwrap_or(0), *downloads))
+    .map(|(major, _)| major.to_string())
+    .collect();
+
+  for summary in snapshots.values_mut() {
+    summary.retain(|s| !hidden.contains(&s.major_version));
+  }
+}
+
+/// Total downloads (summed across major versions) per snapshot date for a module
+pub(crate) fn daily_totals(mod_path: &Path, rules: &Versions) -> Result<BTreeMap<NaiveDate, u64>> {
+  Ok(
+    load_snapshots(mod_path, rules)?
+      .into_iter()
+      .map(|(date, summary)| (date, summary.iter().map(|s| s.downloads).sum()))
+      .collect(),
+  )
+}
 
-  for fentry in fs::read_dir(mod_path)? {
-    let file_path = fentry?.path();
-    let file_name = file_path
-      .file_stem()
-      .ok_or_else(|| anyhow::anyhow!("Missing file stem for path: {:?}", file_path))?
-      .to_str()
-      .ok_or_else(|| anyhow::anyhow!("Non-UTF8 file stem for path: {:?}", file_path))?;
-    let file_data = fs::read_to_string(&file_path)?;
-    let summary = serde_json::from_str::<Vec<Summary>>(&file_data)?;
+/// Downloads gained per complete month for every module
+///
+/// The registry only reports cumulative totals, so the monthly value is the difference between the last snapshot
+/// of a month and the last snapshot of the month before it. The first month of a module has no baseline and is
+/// omitted.
+pub(crate) fn monthly_downloads(
+  data_path: &Path,
+  versions: &VersionConfig,
+  clock: &Clock,
+) -> Result<BTreeMap<String, BTreeMap<NaiveDate, u64>>> {
+  let mut downloads = BTreeMap::new();
+
+  for module in modules(data_path)? {
+    let daily = daily_totals(&data_path.join("registry").join(&module), &versions.rules(&module))?;
+    let month_end = ChartOptions::default().complete_buckets(&daily, true, clock);
+    let dates: Vec<NaiveDate> = month_end.keys().copied().collect();
+    let values: Vec<u64> = month_end.values().copied().collect();
+
+    let gained = dates
+      .iter()
+      .skip(1)
+      .zip(values.windows(2))
+      .map(|(d, w)| (*d, w[1].saturating_sub(w[0])))
+      .collect();
+    downloads.insert(module, gained);
+  }
 
-    let timestamp = NaiveDate::parse_from_str(file_name, "%Y-%m-%d")?;
+  Ok(downloads)
+}
+
+/// Total downloads per module, summed across major versions, in the complete buckets of the chart options
+pub(crate) fn downloads(
+  data_path: &Path,
+  options: &ChartOptions,
+  versions: &VersionConfig,
+  clock: &Clock,
+) -> Result<BTreeMap<String, BTreeMap<NaiveDate, u64>>> {
+  let mut downloads = BTreeMap::new();
+  for module in modules(data_path)? {
+    let daily = daily_totals(&data_path.join("registry").join(&module), &versions.rules(&module))?;
+    downloads.insert(module, options.complete_buckets(&daily, true, clock));
+  }
 
+  Ok(downloads)
+}
+
+/// Download traces of the major versions the rules show
+fn get_module_data_traces(
+  mod_path: &Path,
+  options: &ChartOptions,
+  rules: &Versions,
+  clock: &Clock,
+) -> Result<Vec<VersionTrace>> {
+  let mut daily: BTreeMap<String, BTreeMap<NaiveDate, u64>> = BTreeMap::new();
+
+  for (timestamp, summary) in load_snapshots(mod_path, rules)? {
     for sum in summary.iter() {
       daily
         .entry(sum.major_version.clone())
@@ -203,21 +386,9 @@ fn get_module_data_traces(mod_path: &Path) -> Result<Vec<VersionTrace>> {
 
   let mut traces = Vec::new();
   for (version, date_values) in daily.into_iter() {
-    if module_name == "eks" && version.parse::<i32>().unwrap_or(0) < 16 {
-      tracing::debug!("Skipping {mod_path:#?} version {version} (pre-v16 EKS)");
-      continue;
-    }
-
-    let mut monthly: BTreeMap<NaiveDate, u64> = BTreeMap::new();
-    for (date, count) in date_values {
-      let month_start =
-        NaiveDate::from_ymd_opt(date.year(), date.month(), 1).ok_or_else(|| anyhow::anyhow!("Invalid date: {date}"))?;
-      monthly.insert(month_start, count);
-    }
-
-    let dates: Vec<NaiveDate> = monthly.keys().copied().collect();
-    let values: Vec<u64> = monthly.values().copied().collect();
-    let (dates, values) = crate::graph::filter_incomplete_month(dates, values);
+    let buckets = options.complete_buckets(&date_values, true, clock);
+    let dates: Vec<NaiveDate> = buckets.keys().copied().collect();
+    let values: Vec<u64> = buckets.values().copied().collect();
 
     traces.push(VersionTrace {
       name: format!("v{version}.0"),
@@ -229,12 +400,33 @@ fn get_module_data_traces(mod_path: &Path) -> Result<Vec<VersionTrace>> {
   Ok(traces)
 }
 
-/// Output JSON data for the Astro site
-pub(crate) fn graph(data_path: &Path, output_path: &Path) -> Result<()> {
-  let timestamp = chrono::Local::now().to_utc().format("%Y-%m-%d %H:%M:%S").to_string();
+/// Chart page with the total downloads of each module, one section per category like the traffic pages
+pub(crate) fn category_page(
+  data_path: &Path,
+  timestamp: &str,
+  options: &ChartOptions,
+  versions: &VersionConfig,
+  categories: &crate::topics::Categories,
+  clock: &Clock,
+) -> Result<crate::graph::ChartPage> {
+  Ok(crate::graph::ChartPage {
+    schema_version: crate::graph::SCHEMA_VERSION,
+    title: "Terraform Registry Downloads per Module".to_string(),
+    updated_at: timestamp.to_string(),
+    sections: crate::graph::category_sections(&downloads(data_path, options, versions, clock)?, categories)?,
+  })
+}
 
+/// Chart page with the downloads of each major version, one section per module
+pub(crate) fn page(
+  data_path: &Path,
+  timestamp: &str,
+  options: &ChartOptions,
+  versions: &VersionConfig,
+  clock: &Clock,
+) -> Result<crate::graph::ChartPage> {
   let title = "Terraform Registry Downloads";
-  let tdata = collect_trace_data(data_path)?;
+  let tdata = collect_trace_data(data_path, options, versions, clock)?;
 
   let mut sections = Vec::new();
   for (module, traces) in tdata.into_iter() {
@@ -253,6 +445,7 @@ pub(crate) fn graph(data_path: &Path, output_path: &Path) -> Result<()> {
         crate::graph::ChartDataset {
           label: t.name,
           data: data_points,
+          forecast: None,
         }
       })
       .collect();
@@ -263,14 +456,12 @@ pub(crate) fn graph(data_path: &Path, output_path: &Path) -> Result<()> {
     });
   }
 
-  let page = crate::graph::ChartPage {
+  Ok(crate::graph::ChartPage {
+    schema_version: crate::graph::SCHEMA_VERSION,
     title: title.to_string(),
-    updated_at: timestamp,
+    updated_at: timestamp.to_string(),
     sections,
-  };
-
-  info!("Writing registry-downloads.json");
-  crate::graph::write_chart_page(output_path, "registry-downloads.json", &page)
+  })
 }
 
 #[cfg(test)]
@@ -343,13 +534,22 @@ mod tests {
       ],
     };
 
-    let summary = response.summarize().unwrap();
+    let summary = response.summarize(&Versions::default());
     // v1 should aggregate downloads: 500 + 300 = 800
     assert_eq!(summary.get("01").unwrap().downloads, 800);
     assert_eq!(summary.get("01").unwrap().created_at, "2023-01-01");
+    assert_eq!(summary.get("01").unwrap().latest_release_at, "2023-06-01");
+    assert_eq!(summary.get("01").unwrap().releases, 2);
     // v2 should have 200 downloads
     assert_eq!(summary.get("02").unwrap().downloads, 200);
     assert_eq!(summary.get("02").unwrap().created_at, "2024-01-01");
+
+    let rules = Versions {
+      min_major: 2,
+      ..Versions::default()
+    };
+    // The minimum major only hides majors when reading, so collection keeps them
+    assert!(response.summarize(&rules).contains_key("01"));
   }
 
   #[test]
@@ -388,13 +588,58 @@ mod tests {
       }],
     };
 
-    let summary = response.summarize().unwrap();
+    let summary = response.summarize(&Versions::default());
     assert!(summary.is_empty(), "v0 versions should be skipped");
+
+    let rules = Versions {
+      include_v0: true,
+      ..Versions::default()
+    };
+    assert_eq!(response.summarize(&rules)["00"].downloads, 50);
   }
 
   #[test]
-  fn test_summarize_invalid_version() {
-    let response = Response {
+  fn test_traces_follow_version_rules() {
+    let path = std::env::temp_dir().join(format!("tmd-registry-{}", std::process::id()));
+    let module = path.join("eks");
+    fs::create_dir_all(&module).unwrap();
+    let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    for (day, date) in start.iter_days().take(60).enumerate() {
+      let summary = serde_json::json!([
+        { "downloads": day * 100, "major_version": "15", "created_at": "2020-01-01" },
+        { "downloads": day * 100, "major_version": "19", "created_at": "2022-01-01" },
+        { "downloads": day, "major_version": "20", "created_at": "2024-01-01" },
+      ]);
+      fs::write(module.join(format!("{date}.json")), summary.to_string()).unwrap();
+    }
+
+    let clock = Clock::as_of(NaiveDate::from_ymd_opt(2024, 6, 1));
+    let names = |rules: &Versions| -> Vec<String> {
+      get_module_data_traces(&module, &ChartOptions::default(), rules, &clock)
+        .unwrap()
+        .into_iter()
+        .map(|t| t.name)
+        .collect()
+    };
+
+    let versions = VersionConfig::default();
+    assert_eq!(names(&versions.rules("eks")), vec!["v19.0", "v20.0"]);
+    let rules = Versions {
+      min_downloads: 1_000,
+      ..versions.rules("eks")
+    };
+    assert_eq!(names(&rules), vec!["v19.0"]);
+
+    // Hidden majors are left out of every reader of the snapshots, not just the charts
+    let totals = daily_totals(&module, &versions.rules("eks")).unwrap();
+    assert_eq!(totals.values().next_back(), Some(&(59 * 100 + 59)));
+
+    fs::remove_dir_all(&path).unwrap();
+  }
+
+  /// Registry response of a module with the given versions, downloads and creation dates
+  fn response(versions: &[(&str, u64, &str)]) -> Response {
+    Response {
       data: Data {
         dtype: "modules".to_string(),
         id: "test-id".to_string(),
@@ -412,23 +657,96 @@ mod tests {
         relationships: serde_json::json!({}),
         links: serde_json::json!({}),
       },
-      included: vec![Included {
-        itype: "module-versions".to_string(),
-        id: "1".to_string(),
-        attributes: IncludedAttributes {
-          created_at: "2023-01-01T00:00:00Z".to_string(),
-          description: "bad version".to_string(),
-          downloads: 10,
-          published_at: "2023-01-01T00:00:00Z".to_string(),
-          source: "".to_string(),
-          updated_at: "2023-01-01T00:00:00Z".to_string(),
-          version: "invalid".to_string(),
-        },
-        links: serde_json::json!({}),
-      }],
+      included: versions
+        .iter()
+        .enumerate()
+        .map(|(id, (version, downloads, created_at))| Included {
+          itype: "module-versions".to_string(),
+          id: id.to_string(),
+          attributes: IncludedAttributes {
+            created_at: format!("{created_at}T00:00:00Z"),
+            description: version.to_string(),
+            downloads: *downloads,
+            published_at: format!("{created_at}T00:00:00Z"),
+            source: "".to_string(),
+            updated_at: format!("{created_at}T00:00:00Z"),
+            version: version.to_string(),
+          },
+          links: serde_json::json!({}),
+        })
+        .collect(),
+    }
+  }
+
+  #[test]
+  fn test_summarize_invalid_version() {
+    let response = response(&[
+      ("invalid", 10, "2023-01-01"),
+      ("1.2", 5, "2023-01-01"),
+      ("v2.0.0", 100, "2023-01-01"),
+      ("2.1.0+build.5", 20, "2023-01-01"),
+      ("3.0.0-beta.1", 7, "2023-01-01"),
+    ]);
+
+    // Invalid versions are skipped instead of failing the module
+    let summary = response.summarize(&Versions::default());
+    assert_eq!(summary.keys().collect::<Vec<_>>(), vec!["02", "03"]);
+    assert_eq!(summary["02"].downloads, 120);
+    assert_eq!(summary["02"].created_at, "2023-01-01");
+    // Prereleases count towards their major, with their share recorded as a breakdown
+    assert_eq!((summary["03"].downloads, summary["03"].prerelease_downloads), (7, 7));
+
+    let rules = Versions {
+      include_prereleases: false,
+      ..Versions::default()
+    };
+    assert!(!response.summarize(&rules).contains_key("03"));
+  }
+
+  #[test]
+  fn test_summarize_invalid_date() {
+    let mut response = response(&[("1.0.0", 5, "2024-01-01"), ("1.1.0", 3, "2024-02-01")]);
+    response.included[1].attributes.created_at = "yesterday".to_string();
+
+    let summary = response.summarize(&Versions::default());
+    assert_eq!((summary["01"].downloads, summary["01"].releases), (5, 1));
+  }
+
+  #[test]
+  fn test_release_matches_v_prefix() {
+    let response = response(&[("v2.0.0", 1, "2024-01-01"), ("2.1.0", 1, "2024-02-01")]);
+    let find = |version: &str| {
+      response
+        .release(&parse_version(version).unwrap())
+        .map(|i| i.attributes.version.as_str())
     };
 
-    let result = response.summarize();
-    assert!(result.is_err(), "Invalid version should return error");
+    assert_eq!(find("2.0.0"), Some("v2.0.0"));
+    assert_eq!(find("v2.1.0"), Some("2.1.0"));
+    assert_eq!(find("3.0.0"), None);
+  }
+
+  #[test]
+  fn test_summarize_release_dates() {
+    let summary = response(&[
+      ("4.2.0", 1, "2024-05-01"),
+      ("4.0.1", 1, "2024-02-01"),
+      ("4.0.0-rc.1", 1, "2024-01-15"),
+      ("5.0.1", 1, "2024-06-01"),
+    ])
+    .summarize(&Versions::default());
+
+    // The first release of a major is its earliest version, whether or not it is X.0.0
+    let v4 = &summary["04"];
+    assert_eq!(
+      (v4.created_at.as_str(), v4.latest_release_at.as_str()),
+      ("2024-01-15", "2024-05-01")
+    );
+    assert_eq!(v4.releases, 3);
+    let v5 = &summary["05"];
+    assert_eq!(
+      (v5.created_at.as_str(), v5.latest_release_at.as_str()),
+      ("2024-06-01", "2024-06-01")
+    );
   }
 }
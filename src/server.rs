@@ -0,0 +1,427 @@
+use std::{
+  collections::BTreeMap,
+  fs,
+  net::SocketAddr,
+  path::{Path, PathBuf},
+  sync::{Arc, RwLock},
+  time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use axum::{
+  Json, Router,
+  extract::{Path as UrlPath, Query, State},
+  http::{StatusCode, header},
+  response::{IntoResponse, Response},
+  routing::get,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+  config::Config,
+  graph::{ChartDataset, ChartOptions, ChartSection, DataPoint, Granularity},
+  registry::Summary,
+  topics::Categories,
+};
+
+type Series = BTreeMap<NaiveDate, u64>;
+
+/// Collected data held in memory, reloaded when the files on disk change
+#[derive(Default)]
+struct Cache {
+  /// Registry snapshots per module, keyed by snapshot date
+  registry: BTreeMap<String, BTreeMap<NaiveDate, Vec<Summary>>>,
+  views: BTreeMap<String, Series>,
+  clones: BTreeMap<String, Series>,
+  /// Categories of every module, reassigned with the collected topics on each reload
+  categories: Categories,
+  /// Latest metrics in the OpenMetrics text format
+  metrics: String,
+  /// Files in the data directory when it was loaded
+  stamp: Stamp,
+}
+
+impl Cache {
+  fn load(data_path: &Path, config: &Config) -> Result<Self> {
+    let categories = Categories::load(data_path, &config.categories)?;
+    let mut cache = Self {
+      stamp: Stamp::of(data_path)?,
+      metrics: crate::metrics::render(data_path, &categories, &config.versions)?,
+      categories,
+      ..Self::default()
+    };
+
+    for module in crate::registry::modules(data_path)? {
+      let snapshots = crate::registry::load_snapshots(
+        &data_path.join("registry").join(&module),
+        &config.versions.rules(&module),
+      )?;
+      cache.registry.insert(module, snapshots);
+    }
+    for module in crate::github::modules(data_path)? {
+      let views = crate::github::daily_traffic(data_path, &module, "views")?;
+      let clones = crate::github::daily_traffic(data_path, &module, "clones")?;
+      cache.views.insert(module.clone(), views);
+      cache.clones.insert(module, clones);
+    }
+
+    Ok(cache)
+  }
+}
+
+/// Number of files below a directory and their most recent modification time, which changes when files are added,
+/// modified or deleted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Stamp {
+  files: usize,
+  modified: Option<SystemTime>,
+}
+
+impl Stamp {
+  fn of(path: &Path) -> Result<Self> {
+    let mut stamp = Self::default();
+    for entry in fs::read_dir(path)? {
+      let entry = entry?;
+      let inner = if entry.file_type()?.is_dir() {
+        Self::of(&entry.path())?
+      } else {
+        Self {
+          files: 1,
+          modified: Some(entry.metadata()?.modified()?),
+        }
+      };
+      stamp.files += inner.files;
+      stamp.modified = stamp.modified.max(inner.modified);
+    }
+    Ok(stamp)
+  }
+}
+
+#[derive(Clone)]
+struct AppState {
+  data_path: Arc<PathBuf>,
+  config: Arc<Config>,
+  cache: Arc<RwLock<Cache>>,
+}
+
+impl AppState {
+  fn new(data_path: PathBuf, config: Config) -> Result<Self> {
+    let cache = Cache::load(&data_path, &config)?;
+    Ok(Self {
+      data_path: Arc::new(data_path),
+      config: Arc::new(config),
+      cache: Arc::new(RwLock::new(cache)),
+    })
+  }
+
+  /// Reload the cache if any file in the data directory changed since it was loaded
+  fn refresh(&self) -> Result<()> {
+    let stamp = Stamp::of(&self.data_path)?;
+    let current = self
+      .cache
+      .read()
+      .map_err(|_| anyhow::anyhow!("Cache lock poisoned"))?
+      .stamp;
+    if stamp != current {
+      info!("Data changed on disk, reloading");
+      let cache = Cache::load(&self.data_path, &self.config)?;
+      *self.cache.write().map_err(|_| anyhow::anyhow!("Cache lock poisoned"))? = cache;
+    }
+    Ok(())
+  }
+}
+
+enum ApiError {
+  NotFound(String),
+  Internal(String),
+}
+
+impl IntoResponse for ApiError {
+  fn into_response(self) -> Response {
+    let (status, message) = match self {
+      Self::NotFound(m) => (StatusCode::NOT_FOUND, m),
+      Self::Internal(m) => (StatusCode::INTERNAL_SERVER_ERROR, m),
+    };
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+  }
+}
+
+impl From<crate::error::Error> for ApiError {
+  fn from(e: crate::error::Error) -> Self {
+    match e {
+      crate::error::Error::NotFound { .. } => Self::NotFound(e.to_string()),
+      _ => Self::Internal(e.to_string()),
+    }
+  }
+}
+
+type ApiResult<T> = std::result::Result<Json<T>, ApiError>;
+
+#[derive(Debug, Deserialize)]
+struct SeriesQuery {
+  from: Option<NaiveDate>,
+  to: Option<NaiveDate>,
+  #[serde(default)]
+  granularity: Granularity,
+  /// `views` or `clones`, for traffic endpoints
+  #[serde(default, rename = "type")]
+  traffic: Option<String>,
+}
+
+impl SeriesQuery {
+  /// Filter a daily series to the requested range and group it into buckets, including the current one
+  fn apply(&self, series: &Series, cumulative: bool) -> Vec<DataPoint> {
+    let options = ChartOptions {
+      granularity: self.granularity,
+      from: self.from,
+      to: self.to,
+    };
+
+    options
+      .buckets(series, cumulative)
+      .into_iter()
+      .map(|(d, y)| DataPoint { x: d.to_string(), y })
+      .collect()
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ModuleInfo {
+  name: String,
+  categories: Vec<String>,
+  registry: bool,
+  traffic: bool,
+}
+
+fn read(state: &AppState) -> std::result::Result<std::sync::RwLockReadGuard<'_, Cache>, ApiError> {
+  state
+    .cache
+    .read()
+    .map_err(|_| ApiError::Internal("Cache lock poisoned".to_string()))
+}
+
+async fn modules(State(state): State<AppState>) -> ApiResult<Vec<ModuleInfo>> {
+  let cache = read(&state)?;
+  let mut names: Vec<&String> = cache.registry.keys().chain(cache.views.keys()).collect();
+  names.sort();
+  names.dedup();
+
+  let modules = names
+    .into_iter()
+    .map(|name| ModuleInfo {
+      name: name.clone(),
+      categories: cache.categories.of(name).into_iter().map(String::from).collect(),
+      registry: cache.registry.contains_key(name),
+      traffic: cache.views.contains_key(name),
+    })
+    .collect();
+
+  Ok(Json(modules))
+}
+
+async fn registry(
+  State(state): State<AppState>,
+  UrlPath(module): UrlPath<String>,
+  Query(query): Query<SeriesQuery>,
+) -> ApiResult<ChartSection> {
+  let cache = read(&state)?;
+  let snapshots = cache
+    .registry
+    .get(&module)
+    .ok_or_else(|| ApiError::NotFound(format!("Unknown module: {module}")))?;
+
+  let mut majors: BTreeMap<u64, Series> = BTreeMap::new();
+  for (date, summary) in snapshots {
+    for s in summary {
+      let major = s.major_version.parse().unwrap_or(0);
+      majors.entry(major).or_default().insert(*date, s.downloads);
+    }
+  }
+
+  let datasets = majors
+    .into_iter()
+    .map(|(major, series)| ChartDataset {
+      label: format!("v{major}.0"),
+      data: query.apply(&series, true),
+      forecast: None,
+    })
+    .collect();
+
+  Ok(Json(ChartSection {
+    title: module,
+    datasets,
+  }))
+}
+
+async fn category_traffic(
+  State(state): State<AppState>,
+  UrlPath(category): UrlPath<String>,
+  Query(query): Query<SeriesQuery>,
+) -> ApiResult<ChartSection> {
+  let cache = read(&state)?;
+  let traffic = match query.traffic.as_deref().unwrap_or("views") {
+    "views" => &cache.views,
+    "clones" => &cache.clones,
+    other => return Err(ApiError::NotFound(format!("Unknown traffic type: {other}"))),
+  };
+
+  let filter = match category.as_str() {
+    "all" => None,
+    c if crate::CATEGORIES.contains_key(c) => Some(c),
+    c => return Err(ApiError::NotFound(format!("Unknown category: {c}"))),
+  };
+
+  let mut datasets = Vec::new();
+  for (module, series) in traffic {
+    if cache.categories.contains(filter, module)? {
+      datasets.push(ChartDataset {
+        label: module.clone(),
+        data: query.apply(series, false),
+        forecast: None,
+      });
+    }
+  }
+
+  Ok(Json(ChartSection {
+    title: category,
+    datasets,
+  }))
+}
+
+async fn metrics(State(state): State<AppState>) -> Result<Response, ApiError> {
+  let cache = read(&state)?;
+  Ok(
+    (
+      [(header::CONTENT_TYPE, crate::metrics::CONTENT_TYPE)],
+      cache.metrics.clone(),
+    )
+      .into_response(),
+  )
+}
+
+fn router(state: AppState) -> Router {
+  Router::new()
+    .route("/metrics", get(metrics))
+    .route("/modules", get(modules))
+    .route("/modules/{module}/registry", get(registry))
+    .route("/categories/{category}/traffic", get(category_traffic))
+    .with_state(state)
+}
+
+/// Serve the collected data over a read-only JSON API, reloading it when files change on disk
+pub(crate) async fn serve(data_path: PathBuf, config: Config, address: SocketAddr, poll: Duration) -> Result<()> {
+  let state = AppState::new(data_path, config)?;
+
+  let watcher = state.clone();
+  tokio::spawn(async move {
+    loop {
+      tokio::time::sleep(poll).await;
+      // Walking and parsing the data directory blocks, so it runs off the async worker threads
+      let watcher = watcher.clone();
+      match tokio::task::spawn_blocking(move || watcher.refresh()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!("Failed to reload data: {e:#}"),
+        Err(e) => error!("Data reload panicked: {e}"),
+      }
+    }
+  });
+
+  let listener = tokio::net::TcpListener::bind(address).await?;
+  info!("Listening on http://{}", listener.local_addr()?);
+  axum::serve(listener, router(state)).await?;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn write_fixture(path: &Path) {
+    let registry = path.join("registry").join("vpc");
+    fs::create_dir_all(&registry).unwrap();
+    for (date, downloads) in [("2024-01-30", 100), ("2024-01-31", 150), ("2024-02-01", 170)] {
+      let summary = format!(r#"[{{"downloads": {downloads}, "major_version": "5", "created_at": "2023-05-01"}}]"#);
+      fs::write(registry.join(format!("{date}.json")), summary).unwrap();
+    }
+
+    let github = path.join("github").join("vpc");
+    fs::create_dir_all(&github).unwrap();
+    let traffic = r#"{
+      "2024-01-30": {"count": 10, "timestamp": "2024-01-30T00:00:00Z", "uniques": 1},
+      "2024-01-31": {"count": 20, "timestamp": "2024-01-31T00:00:00Z", "uniques": 2},
+      "2024-02-01": {"count": 5, "timestamp": "2024-02-01T00:00:00Z", "uniques": 1}
+    }"#;
+    fs::write(github.join("views.json"), traffic).unwrap();
+    fs::write(github.join("clones.json"), traffic).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_api() {
+    let data_path = std::env::temp_dir().join(format!("tmd-server-{}", std::process::id()));
+    write_fixture(&data_path);
+
+    let state = AppState::new(data_path.clone(), Config::default()).unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(axum::serve(listener, router(state.clone())).into_future());
+
+    let get = |path: &str| {
+      let url = format!("{base}{path}");
+      async move { reqwest::get(url).await.unwrap() }
+    };
+
+    let modules: Vec<ModuleInfo> = get("/modules").await.json().await.unwrap();
+    assert_eq!(modules[0].name, "vpc");
+    assert_eq!(modules[0].categories, vec!["networking"]);
+
+    let section: serde_json::Value = get("/modules/vpc/registry?to=2024-01-31").await.json().await.unwrap();
+    assert_eq!(
+      section["datasets"][0]["data"],
+      serde_json::json!([{ "x": "2024-01-01", "y": 150 }])
+    );
+
+    let section: serde_json::Value = get("/categories/networking/traffic?granularity=day&from=2024-01-31")
+      .await
+      .json()
+      .await
+      .unwrap();
+    assert_eq!(
+      section["datasets"][0]["data"][1],
+      serde_json::json!({ "x": "2024-02-01", "y": 5 })
+    );
+
+    let section: serde_json::Value = get("/categories/all/traffic?type=clones").await.json().await.unwrap();
+    assert_eq!(section["datasets"][0]["data"][0]["y"], 30);
+
+    let metrics = get("/metrics").await;
+    assert_eq!(metrics.headers()["content-type"], crate::metrics::CONTENT_TYPE);
+    assert!(
+      metrics
+        .text()
+        .await
+        .unwrap()
+        .contains("tmd_github_views{module=\"vpc\",category=\"networking\"} 5\n")
+    );
+
+    assert_eq!(get("/modules/nope/registry").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(get("/categories/nope/traffic").await.status(), StatusCode::NOT_FOUND);
+
+    // New files on disk are picked up on the next refresh
+    fs::create_dir_all(data_path.join("registry").join("eks")).unwrap();
+    fs::write(data_path.join("registry").join("eks").join("2024-02-01.json"), "[]").unwrap();
+    state.refresh().unwrap();
+    let modules: Vec<ModuleInfo> = get("/modules").await.json().await.unwrap();
+    assert_eq!(modules.len(), 2);
+
+    // Deleted files are picked up too, even though no remaining file is newer
+    fs::remove_dir_all(data_path.join("registry").join("eks")).unwrap();
+    state.refresh().unwrap();
+    let modules: Vec<ModuleInfo> = get("/modules").await.json().await.unwrap();
+    assert_eq!(modules.len(), 1);
+
+    fs::remove_dir_all(data_path).unwrap();
+  }
+}
@@ -0,0 +1,324 @@
+//! Typed, read-only access to the collected data directory
+//!
+//! ```
+//! use terraform_module_data::store::{DataStore, TrafficKind};
+//!
+//! # fn main() -> terraform_module_data::error::Result<()> {
+//! # let data = std::env::temp_dir().join(format!("tmd-store-doc-{}", std::process::id()));
+//! # std::fs::create_dir_all(data.join("registry/vpc")).unwrap();
+//! # std::fs::create_dir_all(data.join("github/vpc")).unwrap();
+//! # std::fs::write(
+//! #   data.join("registry/vpc/2024-06-01.json"),
+//! #   r#"[{ "downloads": 100, "major_version": "5", "created_at": "2023-05-30" }]"#,
+//! # ).unwrap();
+//! # std::fs::write(
+//! #   data.join("github/vpc/views.json"),
+//! #   r#"{ "2024-06-01": { "count": 12, "timestamp": "2024-06-01T00:00:00Z", "uniques": 3 } }"#,
+//! # ).unwrap();
+//! let store = DataStore::open(&data);
+//! for module in store.modules()?.iter().take(3) {
+//!   let latest = store.snapshots(module, ..)?.last();
+//!   let views: u64 = store
+//!     .traffic(module, TrafficKind::Views, ..)?
+//!     .map(|t| t.count)
+//!     .sum();
+//!   println!(
+//!     "{module}: {:?} downloads, {views} views",
+//!     latest.map(|s| s.total())
+//!   );
+//! }
+//! # std::fs::remove_dir_all(&data).unwrap();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  ops::RangeBounds,
+  path::{Path, PathBuf},
+};
+
+use chrono::NaiveDate;
+
+use crate::{
+  config::VersionConfig,
+  error::{Error, Result},
+  graph::Granularity,
+};
+
+/// A data directory written by `tmd collect-data`
+#[derive(Clone, Debug)]
+pub struct DataStore {
+  root: PathBuf,
+  versions: VersionConfig,
+}
+
+/// Registry downloads of every major version of a module on a single day
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Snapshot {
+  pub date: NaiveDate,
+  /// Sorted by major version
+  pub majors: Vec<MajorDownloads>,
+}
+
+impl Snapshot {
+  /// Downloads summed across all major versions
+  pub fn total(&self) -> u64 {
+    self.majors.iter().map(|m| m.downloads).sum()
+  }
+}
+
+/// Cumulative registry downloads of a major version
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MajorDownloads {
+  pub major: u64,
+  pub downloads: u64,
+  /// Release date of the `x.0.0` version, when known
+  pub created_at: Option<NaiveDate>,
+}
+
+/// GitHub repository traffic of a module on a single day
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DailyTraffic {
+  pub date: NaiveDate,
+  pub count: u64,
+  pub uniques: u64,
+}
+
+/// Kind of GitHub repository traffic
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TrafficKind {
+  Views,
+  Clones,
+}
+
+impl TrafficKind {
+  fn file_stem(self) -> &'static str {
+    match self {
+      Self::Views => "views",
+      Self::Clones => "clones",
+    }
+  }
+}
+
+/// How the values within a time bucket are combined
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Aggregation {
+  /// Sum the values, for daily counts such as traffic
+  Sum,
+  /// Keep the last value, for cumulative series such as registry downloads
+  Last,
+}
+
+impl DataStore {
+  /// Open a data directory with the default version rules; nothing is read until data is requested
+  pub fn open(root: impl Into<PathBuf>) -> Self {
+    Self {
+      root: root.into(),
+      versions: VersionConfig::default(),
+    }
+  }
+
+  /// Use the version rules of a configuration to decide which major versions the snapshots include
+  pub fn with_versions(mut self, versions: VersionConfig) -> Self {
+    self.versions = versions;
+    self
+  }
+
+  pub fn root(&self) -> &Path {
+    &self.root
+  }
+
+  /// Names of every module with registry or traffic data, sorted
+  pub fn modules(&self) -> Result<Vec<String>> {
+    let mut modules = BTreeSet::new();
+    if self.root.join("registry").is_dir() {
+      modules.extend(crate::registry::modules(&self.root)?);
+    }
+    if self.root.join("github").is_dir() {
+      modules.extend(crate::github::modules(&self.root)?);
+    }
+    Ok(modules.into_iter().collect())
+  }
+
+  /// Registry snapshots of a module within a date range, oldest first, without the majors the version rules hide
+  ///
+  /// ```
+  /// # use terraform_module_data::store::DataStore;
+  /// # fn main() -> terraform_module_data::error::Result<()> {
+  /// use chrono::NaiveDate;
+  ///
+  /// # let data = std::env::temp_dir().join(format!("tmd-snapshots-doc-{}", std::process::id()));
+  /// # std::fs::create_dir_all(data.join("registry/vpc")).unwrap();
+  /// # for date in ["2024-05-31", "2024-06-01", "2024-07-01"] {
+  /// #   std::fs::write(
+  /// #     data.join(format!("registry/vpc/{date}.json")),
+  /// #     r#"[
+  /// #       { "downloads": 1, "major_version": "0", "created_at": "2020-01-01" },
+  /// #       { "downloads": 100, "major_version": "5", "created_at": "2023-05-30" }
+  /// #     ]"#,
+  /// #   ).unwrap();
+  /// # }
+  /// let store = DataStore::open(&data);
+  /// let from = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+  /// let to = NaiveDate::from_ymd_opt(2024, 6, 30).unwrap();
+  /// for snapshot in store.snapshots("vpc", from..=to)? {
+  ///   assert!(snapshot.date >= from && snapshot.date <= to);
+  ///   assert!(snapshot.majors.iter().all(|m| m.major > 0));
+  /// }
+  /// # std::fs::remove_dir_all(&data).unwrap();
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn snapshots<R: RangeBounds<NaiveDate>>(
+    &self,
+    module: &str,
+    range: R,
+  ) -> Result<impl Iterator<Item = Snapshot> + use<R>> {
+    let mod_path = self.root.join("registry").join(module);
+    let snapshots = crate::registry::load_snapshots(&mod_path, &self.versions.rules(module))?;
+
+    let mut typed = Vec::new();
+    for (date, summary) in snapshots.iter().filter(|(d, _)| range.contains(*d)) {
+      let mut majors = summary
+        .iter()
+        .map(|s| {
+          Ok(MajorDownloads {
+            major: s
+              .major_version
+              .parse()
+              .map_err(|e| Error::parse(format!("major version {} of {module} on {date}", s.major_version), e))?,
+            downloads: s.downloads,
+            created_at: NaiveDate::parse_from_str(&s.created_at, "%Y-%m-%d").ok(),
+          })
+        })
+        .collect::<Result<Vec<_>>>()?;
+      majors.sort_by_key(|m| m.major);
+      typed.push(Snapshot { date: *date, majors });
+    }
+
+    Ok(typed.into_iter())
+  }
+
+  /// Daily GitHub traffic of a module within a date range, oldest first
+  pub fn traffic<R: RangeBounds<NaiveDate>>(
+    &self,
+    module: &str,
+    kind: TrafficKind,
+    range: R,
+  ) -> Result<impl Iterator<Item = DailyTraffic> + use<R>> {
+    let mut typed = Vec::new();
+    for entry in crate::github::load_traffic(&self.root, module, kind.file_stem())?.into_values() {
+      let date = chrono::DateTime::parse_from_rfc3339(&entry.timestamp)
+        .map_err(|e| Error::parse(format!("{kind:?} timestamp {} of {module}", entry.timestamp), e))?
+        .date_naive();
+      if range.contains(&date) {
+        typed.push(DailyTraffic {
+          date,
+          count: entry.count,
+          uniques: entry.uniques,
+        });
+      }
+    }
+    typed.sort_by_key(|t| t.date);
+
+    Ok(typed.into_iter())
+  }
+
+  /// Total registry downloads of a module per snapshot date, within a date range
+  pub fn downloads(&self, module: &str, range: impl RangeBounds<NaiveDate>) -> Result<BTreeMap<NaiveDate, u64>> {
+    Ok(self.snapshots(module, range)?.map(|s| (s.date, s.total())).collect())
+  }
+}
+
+/// Group a daily series into time buckets, keyed by the first day of each bucket
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use terraform_module_data::{
+///   graph::Granularity,
+///   store::{Aggregation, aggregate},
+/// };
+///
+/// let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+/// let daily = [(day(1), 2), (day(2), 3), (day(8), 4)];
+///
+/// let weekly = aggregate(daily, Granularity::Week, Aggregation::Sum);
+/// assert_eq!(
+///   weekly.into_iter().collect::<Vec<_>>(),
+///   vec![(day(1), 5), (day(8), 4)]
+/// );
+///
+/// let monthly = aggregate(daily, Granularity::Month, Aggregation::Last);
+/// assert_eq!(monthly[&day(1)], 4);
+/// ```
+pub fn aggregate(
+  series: impl IntoIterator<Item = (NaiveDate, u64)>,
+  granularity: Granularity,
+  aggregation: Aggregation,
+) -> BTreeMap<NaiveDate, u64> {
+  let mut buckets = BTreeMap::new();
+  for (date, value) in series {
+    let bucket = buckets.entry(granularity.bucket(date)).or_insert(0);
+    *bucket = match aggregation {
+      Aggregation::Sum => *bucket + value,
+      Aggregation::Last => value,
+    };
+  }
+  buckets
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use super::*;
+
+  #[test]
+  fn test_store() {
+    let root = std::env::temp_dir().join(format!("tmd-store-{}", std::process::id()));
+    let registry = root.join("registry").join("eks");
+    fs::create_dir_all(&registry).unwrap();
+    fs::write(
+      registry.join("2024-01-31.json"),
+      r#"[{"downloads": 5, "major_version": "20", "created_at": "2024-01-01"},
+          {"downloads": 100, "major_version": "19", "created_at": "2023-01-01"}]"#,
+    )
+    .unwrap();
+    let github = root.join("github").join("s3-bucket");
+    fs::create_dir_all(&github).unwrap();
+    fs::write(
+      github.join("views.json"),
+      r#"{
+        "2024-01-31": {"count": 20, "timestamp": "2024-01-31T00:00:00Z", "uniques": 2},
+        "2024-01-30": {"count": 10, "timestamp": "2024-01-30T00:00:00Z", "uniques": 1}
+      }"#,
+    )
+    .unwrap();
+
+    let store = DataStore::open(&root);
+    assert_eq!(store.modules().unwrap(), vec!["eks", "s3-bucket"]);
+
+    let snapshots: Vec<Snapshot> = store.snapshots("eks", ..).unwrap().collect();
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].majors[0].major, 19);
+    assert_eq!(snapshots[0].majors[1].created_at, NaiveDate::from_ymd_opt(2024, 1, 1));
+    assert_eq!(snapshots[0].total(), 105);
+
+    let from = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+    let views: Vec<DailyTraffic> = store
+      .traffic("s3-bucket", TrafficKind::Views, from..)
+      .unwrap()
+      .collect();
+    assert_eq!(views.len(), 1);
+    assert_eq!((views[0].count, views[0].uniques), (20, 2));
+    assert!(store.traffic("s3-bucket", TrafficKind::Clones, ..).is_err());
+
+    fs::remove_dir_all(&root).unwrap();
+  }
+}
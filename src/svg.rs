@@ -0,0 +1,273 @@
+use std::{collections::BTreeSet, fmt::Write as _};
+
+use chrono::NaiveDate;
+
+use crate::graph::{ChartPage, ChartSection};
+
+/// Colors assigned to datasets, chosen by a hash of the dataset label so a series keeps its color across charts
+const PALETTE: [&str; 20] = [
+  "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf",
+  "#aec7e8", "#ffbb78", "#98df8a", "#ff9896", "#c5b0d5", "#c49c94", "#f7b6d2", "#c7c7c7", "#dbdb8d", "#9edae5",
+];
+
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 360.0;
+const MARGIN_TOP: f64 = 40.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_BOTTOM: f64 = 40.0;
+const MARGIN_LEFT: f64 = 60.0;
+const LEGEND_ROW: f64 = 18.0;
+const LEGEND_COLUMNS: usize = 4;
+const Y_TICKS: usize = 5;
+const MAX_X_TICKS: usize = 8;
+
+/// Stable color for a dataset label (FNV-1a hash into the palette)
+pub(crate) fn color(label: &str) -> &'static str {
+  let hash = label.bytes().fold(0xcbf29ce484222325_u64, |h, b| {
+    (h ^ b as u64).wrapping_mul(0x100000001b3)
+  });
+  PALETTE[(hash % PALETTE.len() as u64) as usize]
+}
+
+/// Escape text for use in XML/HTML content and attributes
+pub(crate) fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+/// Round a tick step up to 1, 2 or 5 times a power of ten
+fn nice_step(max: f64, ticks: usize) -> f64 {
+  let raw = (max / ticks as f64).max(1.0);
+  let magnitude = 10_f64.powf(raw.log10().floor());
+  let step = [1.0, 2.0, 5.0, 10.0]
+    .into_iter()
+    .find(|m| m * magnitude >= raw)
+    .unwrap_or(10.0);
+  step * magnitude
+}
+
+/// Compact axis label, such as `950`, `12.5k` or `3M`
+pub(crate) fn compact(value: f64) -> String {
+  let (scaled, suffix) = match value {
+    v if v >= 1e9 => (v / 1e9, "B"),
+    v if v >= 1e6 => (v / 1e6, "M"),
+    v if v >= 1e3 => (v / 1e3, "k"),
+    v => (v, ""),
+  };
+  let text = format!("{scaled:.1}");
+  format!("{}{suffix}", text.trim_end_matches(".0"))
+}
+
+/// Format an x value for the axis, `Jan 2024` for dates and the raw value otherwise
+fn x_label(x: &str) -> String {
+  NaiveDate::parse_from_str(x, "%Y-%m-%d")
+    .map(|d| d.format("%b %Y").to_string())
+    .unwrap_or_else(|_| x.to_string())
+}
+
+/// Render a chart section as an SVG line chart with axes, a title and a legend, one line per dataset
+///
+/// Dates are spaced by time when every x value is a date, otherwise x values are spaced evenly.
+pub(crate) fn section(section: &ChartSection) -> String {
+  let xs: Vec<&str> = section
+    .datasets
+    .iter()
+    .flat_map(|d| d.data.iter().map(|p| p.x.as_str()))
+    .collect::<BTreeSet<_>>()
+    .into_iter()
+    .collect();
+  let dates: Option<Vec<NaiveDate>> = xs
+    .iter()
+    .map(|x| NaiveDate::parse_from_str(x, "%Y-%m-%d").ok())
+    .collect();
+
+  let max = section
+    .datasets
+    .iter()
+    .flat_map(|d| d.data.iter().map(|p| p.y))
+    .max()
+    .unwrap_or(0) as f64;
+  let step = nice_step(max, Y_TICKS);
+  let top = (max / step).ceil().max(1.0) * step;
+
+  let (plot_w, plot_h) = (WIDTH - MARGIN_LEFT - MARGIN_RIGHT, HEIGHT - MARGIN_TOP - MARGIN_BOTTOM);
+  let position = |x: &str| -> f64 {
+    let i = xs.iter().position(|v| *v == x).unwrap_or(0);
+    match &dates {
+      Some(d) if d.len() > 1 => {
+        let span = (d[d.len() - 1] - d[0]).num_days().max(1) as f64;
+        (d[i] - d[0]).num_days() as f64 / span
+      }
+      _ => i as f64 / (xs.len().max(2) - 1) as f64,
+    }
+  };
+  let x_of = |x: &str| MARGIN_LEFT + position(x) * plot_w;
+  let y_of = |y: f64| MARGIN_TOP + plot_h - y / top * plot_h;
+
+  let legend_height = LEGEND_ROW * section.datasets.len().div_ceil(LEGEND_COLUMNS) as f64;
+  let mut svg = String::new();
+  let _ = write!(
+    svg,
+    r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {WIDTH} {0}" width="{WIDTH}" height="{0}" font-family="sans-serif" role="img">"#,
+    HEIGHT + legend_height
+  );
+  let _ = write!(
+    svg,
+    r##"<rect width="100%" height="100%" fill="#fff"/><text x="{MARGIN_LEFT}" y="24" font-size="16" font-weight="bold">{}</text>"##,
+    escape(&section.title)
+  );
+
+  // Y axis gridlines and labels
+  let mut tick = 0.0;
+  while tick <= top {
+    let y = y_of(tick);
+    let _ = write!(
+      svg,
+      r##"<line x1="{MARGIN_LEFT}" y1="{y:.1}" x2="{:.1}" y2="{y:.1}" stroke="#e5e5e5"/><text x="{:.1}" y="{:.1}" font-size="11" text-anchor="end" fill="#555">{}</text>"##,
+      MARGIN_LEFT + plot_w,
+      MARGIN_LEFT - 6.0,
+      y + 4.0,
+      compact(tick)
+    );
+    tick += step;
+  }
+
+  // X axis labels, thinned out to at most MAX_X_TICKS
+  let every = xs.len().div_ceil(MAX_X_TICKS).max(1);
+  for x in xs.iter().step_by(every) {
+    let _ = write!(
+      svg,
+      r##"<text x="{:.1}" y="{:.1}" font-size="11" text-anchor="middle" fill="#555">{}</text>"##,
+      x_of(x),
+      MARGIN_TOP + plot_h + 18.0,
+      escape(&x_label(x))
+    );
+  }
+  let _ = write!(
+    svg,
+    r##"<line x1="{MARGIN_LEFT}" y1="{0:.1}" x2="{1:.1}" y2="{0:.1}" stroke="#999"/><line x1="{MARGIN_LEFT}" y1="{MARGIN_TOP}" x2="{MARGIN_LEFT}" y2="{0:.1}" stroke="#999"/>"##,
+    MARGIN_TOP + plot_h,
+    MARGIN_LEFT + plot_w
+  );
+
+  for dataset in &section.datasets {
+    let points: Vec<String> = dataset
+      .data
+      .iter()
+      .map(|p| format!("{:.1},{:.1}", x_of(&p.x), y_of(p.y as f64)))
+      .collect();
+    let _ = write!(
+      svg,
+      r#"<polyline fill="none" stroke="{}" stroke-width="2" points="{}"><title>{}</title></polyline>"#,
+      color(&dataset.label),
+      points.join(" "),
+      escape(&dataset.label)
+    );
+  }
+
+  for (i, dataset) in section.datasets.iter().enumerate() {
+    let (x, y) = (
+      MARGIN_LEFT + (i % LEGEND_COLUMNS) as f64 * plot_w / LEGEND_COLUMNS as f64,
+      HEIGHT + (i / LEGEND_COLUMNS) as f64 * LEGEND_ROW,
+    );
+    let _ = write!(
+      svg,
+      r#"<rect x="{x:.1}" y="{:.1}" width="10" height="10" fill="{}"/><text x="{:.1}" y="{y:.1}" font-size="12">{}</text>"#,
+      y - 9.0,
+      color(&dataset.label),
+      x + 14.0,
+      escape(&dataset.label)
+    );
+  }
+
+  svg.push_str("</svg>");
+  svg
+}
+
+/// Render every section of a chart page, keyed by a file-safe section name
+pub(crate) fn page(page: &ChartPage) -> Vec<(String, String)> {
+  page
+    .sections
+    .iter()
+    .map(|s| {
+      let name: String = s
+        .title
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '-' })
+        .collect();
+      (name, section(s))
+    })
+    .collect()
+}
+
+/// Rasterize an SVG document into a PNG image
+#[cfg(feature = "png")]
+pub(crate) fn png(svg: &str) -> anyhow::Result<Vec<u8>> {
+  use resvg::{tiny_skia, usvg};
+
+  let mut options = usvg::Options::default();
+  options.fontdb_mut().load_system_fonts();
+  let tree = usvg::Tree::from_str(svg, &options)?;
+
+  let size = tree.size().to_int_size();
+  let mut pixmap =
+    tiny_skia::Pixmap::new(size.width(), size.height()).ok_or_else(|| anyhow::anyhow!("Invalid image size"))?;
+  resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+
+  Ok(pixmap.encode_png()?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::graph::{ChartDataset, DataPoint};
+
+  #[test]
+  fn test_color_is_stable() {
+    assert_eq!(color("eks"), color("eks"));
+    assert!(PALETTE.contains(&color("vpc")));
+  }
+
+  #[test]
+  fn test_axis_labels() {
+    assert_eq!(nice_step(1234.0, 5), 500.0);
+    assert_eq!(nice_step(0.0, 5), 1.0);
+    assert_eq!(compact(950.0), "950");
+    assert_eq!(compact(12_500.0), "12.5k");
+    assert_eq!(compact(3_000_000.0), "3M");
+    assert_eq!(x_label("2024-01-01"), "Jan 2024");
+    assert_eq!(x_label("v1.0"), "v1.0");
+  }
+
+  #[test]
+  fn test_section_escapes_labels() {
+    let chart = ChartSection {
+      title: "a < b".to_string(),
+      datasets: vec![ChartDataset {
+        label: "v1 & v2".to_string(),
+        data: vec![
+          DataPoint {
+            x: "2024-01-01".to_string(),
+            y: 1,
+          },
+          DataPoint {
+            x: "2024-02-01".to_string(),
+            y: 3,
+          },
+        ],
+        forecast: None,
+      }],
+    };
+
+    let svg = section(&chart);
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains(">a &lt; b</text>"));
+    assert!(svg.contains("v1 &amp; v2"));
+    assert!(svg.contains(">Feb 2024</text>"));
+    assert!(svg.contains(r#"points="60.0,226.7 700.0,40.0""#));
+  }
+}
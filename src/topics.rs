@@ -0,0 +1,227 @@
+use std::{
+  collections::{BTreeMap, BTreeSet, HashMap},
+  path::Path,
+};
+
+use crate::{
+  config::CategoryConfig,
+  error::{Error, Result},
+};
+
+/// Primary category and all categories of a module
+type Tags = (Option<&'static str>, Vec<&'static str>);
+
+/// Categories of every module, resolved once from the built-in sets, collected topics and configuration
+///
+/// Without a topic mapping or overrides every lookup uses the built-in categories.
+#[derive(Clone, Debug, Default)]
+pub struct Categories {
+  assigned: Option<HashMap<String, Tags>>,
+}
+
+impl Categories {
+  /// The built-in categories only
+  pub fn builtin() -> Self {
+    Self::default()
+  }
+
+  /// Apply the topic mapping and overrides to the modules with data or a built-in category
+  pub fn load(data_path: &Path, config: &CategoryConfig) -> Result<Self> {
+    if config.topics.is_empty() && config.overrides.is_empty() {
+      return Ok(Self::builtin());
+    }
+
+    let assigned = assignments(data_path, config)?
+      .into_iter()
+      .map(|(module, a)| (module, (a.primary, a.categories())))
+      .collect();
+    Ok(Self {
+      assigned: Some(assigned),
+    })
+  }
+
+  /// Categories a module belongs to, sorted by name
+  pub fn of(&self, module: &str) -> Vec<&'static str> {
+    match &self.assigned {
+      Some(assigned) => assigned.get(module).map(|(_, c)| c.clone()).unwrap_or_default(),
+      None => crate::builtin_categories(module),
+    }
+  }
+
+  /// The one category a module is counted in when categories are compared or summed, so no module is counted twice
+  pub fn primary(&self, module: &str) -> Option<&'static str> {
+    match &self.assigned {
+      Some(assigned) => assigned.get(module).and_then(|(p, _)| *p),
+      None => crate::builtin_primary(module),
+    }
+  }
+
+  /// Whether a module belongs to a category (`None` matches every module)
+  pub fn contains(&self, category: Option<&str>, module: &str) -> Result<bool> {
+    match category {
+      Some(c) => Ok(self.of(module).contains(&self::category(c)?)),
+      None => Ok(true),
+    }
+  }
+}
+
+/// How a module's categories were decided
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Assignment {
+  /// Built-in categories, or the configured override
+  pub(crate) manual: BTreeSet<&'static str>,
+  /// Categories of the module's repository topics
+  pub(crate) topics: BTreeSet<&'static str>,
+  pub(crate) overridden: bool,
+  /// First category of the override, the built-in primary category, or the first topic category
+  pub(crate) primary: Option<&'static str>,
+}
+
+impl Assignment {
+  /// Categories the module is shown in: the override, or the built-in and topic categories together
+  pub(crate) fn categories(&self) -> Vec<&'static str> {
+    if self.overridden {
+      self.manual.iter().copied().collect()
+    } else {
+      self.manual.union(&self.topics).copied().collect()
+    }
+  }
+
+  /// Topic categories that the built-in categories or override disagree with
+  pub(crate) fn conflicts(&self) -> Vec<&'static str> {
+    if self.manual.is_empty() {
+      return Vec::new();
+    }
+    self.topics.difference(&self.manual).copied().collect()
+  }
+}
+
+/// The static name of a known category
+fn category(name: &str) -> Result<&'static str> {
+  crate::CATEGORIES
+    .get_key_value(name)
+    .map(|(c, _)| *c)
+    .ok_or_else(|| Error::NotFound {
+      what: format!("Category {name}"),
+    })
+}
+
+/// Decide the categories of a module from its built-in categories, topics and the configuration
+pub(crate) fn assign(config: &CategoryConfig, module: &str, topics: &[String]) -> Result<Assignment> {
+  let topics: BTreeSet<&str> = topics
+    .iter()
+    .filter_map(|t| config.topics.get(t))
+    .map(|c| category(c))
+    .collect::<Result<_>>()?;
+
+  let (manual, overridden, primary) = match config.overrides.get(module) {
+    Some(categories) => {
+      let categories = categories.iter().map(|c| category(c)).collect::<Result<Vec<_>>>()?;
+      let primary = categories.first().copied();
+      (categories.into_iter().collect(), true, primary)
+    }
+    None => (
+      crate::builtin_categories(module).into_iter().collect(),
+      false,
+      crate::builtin_primary(module),
+    ),
+  };
+
+  Ok(Assignment {
+    manual,
+    primary: primary.or_else(|| topics.first().copied()),
+    topics,
+    overridden,
+  })
+}
+
+/// Assignments of every module with data or a built-in category
+pub(crate) fn assignments(data_path: &Path, config: &CategoryConfig) -> Result<BTreeMap<String, Assignment>> {
+  let mut modules: BTreeSet<String> = crate::CATEGORIES.values().flatten().map(|m| m.to_string()).collect();
+  if data_path.join("github").is_dir() {
+    modules.extend(crate::github::modules(data_path)?);
+  }
+  if data_path.join("registry").is_dir() {
+    modules.extend(crate::registry::modules(data_path)?);
+  }
+
+  modules
+    .into_iter()
+    .map(|module| {
+      let topics = crate::github::load_topics(data_path, &module)?;
+      let assignment = assign(config, &module, &topics)?;
+      Ok((module, assignment))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn config() -> CategoryConfig {
+    CategoryConfig {
+      topics: BTreeMap::from([
+        ("kubernetes".to_string(), "compute".to_string()),
+        ("serverless".to_string(), "serverless".to_string()),
+      ]),
+      overrides: BTreeMap::from([("vpc".to_string(), vec!["networking".to_string(), "other".to_string()])]),
+    }
+  }
+
+  #[test]
+  fn test_assign() {
+    let config = config();
+    let topics = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    // Topics agreeing with the built-in category
+    let eks = assign(&config, "eks", &topics(&["aws", "kubernetes"])).unwrap();
+    assert_eq!(eks.categories(), vec!["compute"]);
+    assert!(eks.conflicts().is_empty());
+
+    // Topics adding a category the built-in sets don't have
+    let rds = assign(&config, "rds", &topics(&["serverless"])).unwrap();
+    assert_eq!(rds.categories(), vec!["data", "serverless"]);
+    assert_eq!(rds.conflicts(), vec!["serverless"]);
+
+    // Overrides replace both
+    let vpc = assign(&config, "vpc", &topics(&["kubernetes"])).unwrap();
+    assert_eq!(vpc.categories(), vec!["networking", "other"]);
+    assert_eq!(vpc.primary, Some("networking"));
+    assert_eq!(assign(&config, "lambda", &[]).unwrap().primary, Some("serverless"));
+
+    // Modules without a built-in category take their topic categories
+    let new = assign(&config, "new", &topics(&["kubernetes"])).unwrap();
+    assert_eq!(new.categories(), vec!["compute"]);
+    assert_eq!(new.primary, Some("compute"));
+    assert!(new.conflicts().is_empty());
+    assert!(assign(&config, "unknown", &[]).unwrap().categories().is_empty());
+  }
+
+  #[test]
+  fn test_categories() {
+    // Without collected topics only the overrides apply
+    let categories = Categories::load(Path::new("no-data"), &config()).unwrap();
+    assert_eq!(categories.of("vpc"), vec!["networking", "other"]);
+    assert_eq!(categories.primary("vpc"), Some("networking"));
+    assert!(categories.contains(Some("other"), "vpc").unwrap());
+
+    let builtin = Categories::builtin();
+    assert_eq!(builtin.of("vpc"), vec!["networking"]);
+    assert!(!builtin.contains(Some("other"), "vpc").unwrap());
+    assert!(matches!(
+      builtin.contains(Some("storage"), "vpc"),
+      Err(Error::NotFound { .. })
+    ));
+  }
+
+  #[test]
+  fn test_unknown_category() {
+    let mut config = config();
+    config.topics.insert("aws".to_string(), "storage".to_string());
+    assert!(matches!(
+      assign(&config, "eks", &["aws".to_string()]),
+      Err(Error::NotFound { .. })
+    ));
+  }
+}
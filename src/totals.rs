@@ -0,0 +1,313 @@
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  fs,
+  path::Path,
+};
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+  clock::Clock,
+  config::VersionConfig,
+  graph::{ChartDataset, ChartOptions, ChartPage, ChartSection, DataPoint, SCHEMA_VERSION},
+  topics::Categories,
+};
+
+/// Label of the total of modules without a category
+const UNCATEGORIZED: &str = "Uncategorized";
+
+pub(crate) type ModuleSeries = BTreeMap<String, BTreeMap<NaiveDate, u64>>;
+
+/// Sum the series of every module into one series per primary category
+///
+/// A module in several categories is only added to its primary one, so the category totals add up to the total of
+/// all modules.
+pub(crate) fn by_primary_category(
+  series: &ModuleSeries,
+  categories: &Categories,
+) -> BTreeMap<&'static str, BTreeMap<NaiveDate, u64>> {
+  let mut totals: BTreeMap<&str, BTreeMap<NaiveDate, u64>> = BTreeMap::new();
+  for (module, values) in series {
+    let title = categories
+      .primary(module)
+      .and_then(|c| crate::CATEGORY_SECTIONS.iter().find(|(_, s)| *s == Some(c)))
+      .map_or(UNCATEGORIZED, |(title, _)| *title);
+    let total = totals.entry(title).or_default();
+    for (date, value) in values {
+      *total.entry(*date).or_insert(0) += value;
+    }
+  }
+  totals
+}
+
+/// One dataset per category total, in the order of the category sections
+pub(crate) fn datasets(totals: &BTreeMap<&str, BTreeMap<NaiveDate, u64>>) -> Vec<ChartDataset> {
+  crate::CATEGORY_SECTIONS
+    .iter()
+    .skip(1)
+    .map(|(title, _)| *title)
+    .chain([UNCATEGORIZED])
+    .filter_map(|title| totals.get(title).map(|values| (title, values)))
+    .map(|(title, values)| ChartDataset {
+      label: title.to_string(),
+      data: values
+        .iter()
+        .map(|(d, v)| DataPoint {
+          x: d.to_string(),
+          y: *v,
+        })
+        .collect(),
+      forecast: None,
+    })
+    .collect()
+}
+
+/// Chart page with the downloads, views and clones of each category, counting every module once
+pub(crate) fn page(
+  data_path: &Path,
+  timestamp: &str,
+  options: &ChartOptions,
+  versions: &VersionConfig,
+  categories: &Categories,
+  clock: &Clock,
+) -> Result<ChartPage> {
+  let series = [
+    (
+      "Terraform Registry Downloads",
+      crate::registry::downloads(data_path, options, versions, clock)?,
+    ),
+    (
+      "Repository Page Views",
+      crate::github::traffic(data_path, "views", options, clock)?,
+    ),
+    (
+      "Repository Clones",
+      crate::github::traffic(data_path, "clones", options, clock)?,
+    ),
+  ];
+
+  Ok(ChartPage {
+    schema_version: SCHEMA_VERSION,
+    title: "Totals per Category".to_string(),
+    updated_at: timestamp.to_string(),
+    sections: series
+      .iter()
+      .map(|(title, modules)| ChartSection {
+        title: title.to_string(),
+        datasets: datasets(&by_primary_category(modules, categories)),
+      })
+      .collect(),
+  })
+}
+
+/// Monthly totals across every module and each module's share of them, written to `organization.json`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Organization {
+  pub schema_version: u32,
+  pub updated_at: String,
+  /// Registry downloads gained, views and clones
+  pub metrics: Vec<Metric>,
+}
+
+/// Complete months of one metric; every `values` and share array lines up with `months`, so the categories or the
+/// modules can be drawn as stacked areas that add up to `total`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Metric {
+  pub name: String,
+  pub months: Vec<String>,
+  pub total: Vec<u64>,
+  /// One series per primary category
+  pub categories: Vec<Share>,
+  pub modules: Vec<Share>,
+}
+
+#[derive(Debug, Serialize, JsonSchema, PartialEq)]
+pub struct Share {
+  pub label: String,
+  /// Primary category of a module, absent for category series
+  pub category: Option<String>,
+  /// Zero for months without data, such as the months before a module was published
+  pub values: Vec<u64>,
+  /// Fraction of the organization total in each month
+  pub share: Vec<f64>,
+  /// Fraction of the module's primary category total in each month, absent for category series
+  pub category_share: Option<Vec<f64>>,
+}
+
+/// Fraction of a total, rounded to keep the output readable; zero when the total is zero
+fn fraction(value: u64, total: u64) -> f64 {
+  if total == 0 {
+    0.0
+  } else {
+    (value as f64 / total as f64 * 1e6).round() / 1e6
+  }
+}
+
+/// Align a module series with the shared months, filling months without data with zero
+fn dense(months: &[NaiveDate], values: &BTreeMap<NaiveDate, u64>) -> Vec<u64> {
+  months.iter().map(|m| values.get(m).copied().unwrap_or(0)).collect()
+}
+
+fn metric(name: &str, series: &ModuleSeries, categories: &Categories) -> Metric {
+  let months: Vec<NaiveDate> = series
+    .values()
+    .flat_map(|v| v.keys().copied())
+    .collect::<BTreeSet<_>>()
+    .into_iter()
+    .collect();
+  let total: Vec<u64> = months
+    .iter()
+    .map(|m| series.values().filter_map(|v| v.get(m)).sum())
+    .collect();
+  let share = |values: &[u64], totals: &[u64]| values.iter().zip(totals).map(|(v, t)| fraction(*v, *t)).collect();
+
+  let by_category = by_primary_category(series, categories);
+  let category_shares: Vec<Share> = datasets(&by_category)
+    .into_iter()
+    .map(|d| {
+      let values = dense(&months, &by_category[d.label.as_str()]);
+      Share {
+        share: share(&values, &total),
+        label: d.label,
+        category: None,
+        values,
+        category_share: None,
+      }
+    })
+    .collect();
+
+  let modules = series
+    .iter()
+    .map(|(module, values)| {
+      let values = dense(&months, values);
+      let category = categories.primary(module);
+      let category_total = category
+        .and_then(|c| crate::CATEGORY_SECTIONS.iter().find(|(_, s)| *s == Some(c)))
+        .map_or(UNCATEGORIZED, |(title, _)| *title);
+      let category_total = dense(&months, &by_category[category_total]);
+      Share {
+        label: module.clone(),
+        category: category.map(String::from),
+        share: share(&values, &total),
+        category_share: Some(share(&values, &category_total)),
+        values,
+      }
+    })
+    .collect();
+
+  Metric {
+    name: name.to_string(),
+    months: months.iter().map(|m| m.to_string()).collect(),
+    total,
+    categories: category_shares,
+    modules,
+  }
+}
+
+/// Organization totals and shares of the monthly downloads gained, views and clones
+pub(crate) fn organization(
+  data_path: &Path,
+  versions: &VersionConfig,
+  categories: &Categories,
+  clock: &Clock,
+) -> Result<Organization> {
+  Ok(Organization {
+    schema_version: SCHEMA_VERSION,
+    updated_at: clock.timestamp(),
+    metrics: vec![
+      metric(
+        "downloads",
+        &crate::registry::monthly_downloads(data_path, versions, clock)?,
+        categories,
+      ),
+      metric(
+        "views",
+        &crate::github::monthly_traffic(data_path, "views", clock)?,
+        categories,
+      ),
+      metric(
+        "clones",
+        &crate::github::monthly_traffic(data_path, "clones", clock)?,
+        categories,
+      ),
+    ],
+  })
+}
+
+/// Write `organization.json`
+pub(crate) fn graph(
+  data_path: &Path,
+  output_path: &Path,
+  versions: &VersionConfig,
+  categories: &Categories,
+  clock: &Clock,
+) -> Result<()> {
+  let organization = organization(data_path, versions, categories, clock)?;
+  fs::write(
+    output_path.join("organization.json"),
+    serde_json::to_string_pretty(&organization)?,
+  )?;
+
+  info!("Wrote organization.json");
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_totals_count_modules_once() {
+    let jan = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let series: ModuleSeries = [
+      ("lambda", 10),
+      ("eks", 5),
+      ("app-runner", 1),
+      ("vpc", 3),
+      ("new-module", 7),
+    ]
+    .into_iter()
+    .map(|(m, v)| (m.to_string(), BTreeMap::from([(jan, v)])))
+    .collect();
+
+    let totals = by_primary_category(&series, &Categories::builtin());
+    // lambda is also tagged compute, but only counts towards its primary category
+    assert_eq!(totals["Serverless"][&jan], 10);
+    assert_eq!(totals["Compute"][&jan], 6);
+    assert_eq!(totals["Networking"][&jan], 3);
+    assert_eq!(totals[UNCATEGORIZED][&jan], 7);
+    assert_eq!(totals.values().map(|t| t[&jan]).sum::<u64>(), 26);
+
+    let labels: Vec<String> = datasets(&totals).into_iter().map(|d| d.label).collect();
+    assert_eq!(labels, vec!["Compute", "Serverless", "Networking", UNCATEGORIZED]);
+  }
+
+  #[test]
+  fn test_shares() {
+    let month = |m| NaiveDate::from_ymd_opt(2024, m, 1).unwrap();
+    let series: ModuleSeries = BTreeMap::from([
+      ("eks".to_string(), BTreeMap::from([(month(1), 30), (month(2), 10)])),
+      ("ecs".to_string(), BTreeMap::from([(month(2), 30)])),
+      ("vpc".to_string(), BTreeMap::from([(month(1), 10), (month(2), 60)])),
+    ]);
+
+    let metric = metric("views", &series, &Categories::builtin());
+    assert_eq!(metric.months, vec!["2024-01-01", "2024-02-01"]);
+    assert_eq!(metric.total, vec![40, 100]);
+
+    let compute = &metric.categories[0];
+    assert_eq!((compute.label.as_str(), &compute.values), ("Compute", &vec![30, 40]));
+    assert_eq!(compute.share, vec![0.75, 0.4]);
+
+    // ecs has no views in January, so its series is zero-filled to line up with the months
+    let ecs = &metric.modules[0];
+    assert_eq!(ecs.values, vec![0, 30]);
+    assert_eq!(ecs.share, vec![0.0, 0.3]);
+    assert_eq!(ecs.category_share, Some(vec![0.0, 0.75]));
+    assert_eq!(ecs.category.as_deref(), Some("compute"));
+  }
+}